@@ -0,0 +1,60 @@
+// What the scanner made of each line, for tracking down which line throws an answer off.
+
+use serde::Serialize;
+use crate::MissingDigits;
+use crate::scanner::{Scanner, Token, TokenKind};
+
+#[derive(Debug,PartialEq,Serialize)]
+pub struct Explanation<'a> {
+  pub line: usize,
+  pub text: &'a str,
+  pub first: Option<Token>,
+  pub last: Option<Token>,
+  // The two digit calibration value. A line with no digit at all has none, unless it counts as zero.
+  pub value: Option<i32>,
+}
+
+pub fn explain<'a>(text: &'a str, scanner: &Scanner, missing_digits: MissingDigits) -> Vec<Explanation<'a>> {
+  let missing_value = match missing_digits {
+    MissingDigits::Zero() => Some(0),
+    MissingDigits::Skip() | MissingDigits::Fail() => None,
+  };
+  text
+    .lines()
+    .enumerate()
+    .map(|(index, line)| {
+      let tokens = scanner.tokens(line);
+      Explanation {
+        line: index + 1,
+        text: line,
+        first: tokens.map(|(first, _)| first),
+        last: tokens.map(|(_, last)| last),
+        value: tokens.map(|(first, last)| first.value * 10 + last.value).or(missing_value),
+      }
+    })
+    .collect()
+}
+
+fn describe(text: &str, token: &Token) -> String {
+  let kind = match token.kind {
+    TokenKind::Digit => "digit",
+    TokenKind::Word => "word",
+  };
+  format!("{:?} {}..{} {} {}", &text[token.start .. token.end], token.start, token.end, kind, token.value)
+}
+
+impl Explanation<'_> {
+  // 3: two1nine -> 29 (first "two" 0..3 word 2, last "nine" 4..8 word 9)
+  pub fn to_text(&self) -> String {
+    match (&self.first, &self.last, self.value) {
+      (Some(first), Some(last), Some(value)) =>
+        format!("{}: {} -> {} (first {}, last {})", self.line, self.text, value, describe(self.text, first), describe(self.text, last)),
+      (_, _, Some(value)) => format!("{}: {} -> NO DIGIT, counted as {}", self.line, self.text, value),
+      _ => format!("{}: {} -> NO DIGIT", self.line, self.text),
+    }
+  }
+
+  pub fn to_json(&self) -> String {
+    serde_json::to_string(self).unwrap()
+  }
+}
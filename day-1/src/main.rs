@@ -1,27 +1,107 @@
+mod explain;
+mod parallel;
+mod scanner;
+
+use std::fmt;
 use std::fs;
+use std::sync::OnceLock;
+use std::time::Instant;
 use regex::Regex;
+use scanner::Scanner;
+
+const ENGLISH: [(&str, i32); 10] = [
+  ("zero", 0), ("one", 1), ("two", 2), ("three", 3), ("four", 4),
+  ("five", 5), ("six", 6), ("seven", 7), ("eight", 8), ("nine", 9),
+];
+
+// The words that count as digits, besides the digits themselves
+#[derive(Debug,Clone,PartialEq)]
+struct Vocabulary {
+  words: Vec<(String, i32)>,
+}
+
+impl Default for Vocabulary {
+  fn default() -> Self {
+    Vocabulary { words: ENGLISH.iter().map(|(word, value)| (word.to_string(), *value)).collect() }
+  }
+}
+
+impl Vocabulary {
+  // One word and its value per line, like "huit 8" or "first 1". Blank lines and lines starting
+  // with # are skipped.
+  fn parse(text: &str) -> Result<Vocabulary, String> {
+    let words = text
+      .lines()
+      .enumerate()
+      .map(|(index, line)| (index + 1, line.trim()))
+      .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+      .map(|(line_number, line)| {
+        let bad_line = || format!("Line {}: expected a word and a digit, got {:?}", line_number, line);
+        let (word, value) = line.rsplit_once(char::is_whitespace).ok_or_else(bad_line)?;
+        let value = value.parse().ok().filter(|value| (0 ..= 9).contains(value)).ok_or_else(bad_line)?;
+        Ok((word.trim().to_string(), value))
+      })
+      .collect::<Result<Vec<_>, String>>()?;
+    if words.is_empty() {
+      return Err("The vocabulary has no words".into())
+    }
+    Ok(Vocabulary { words })
+  }
+
+  fn from_file(path: &str) -> Result<Vocabulary, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    Vocabulary::parse(&text)
+  }
+
+  fn value(&self, word: &str) -> Option<i32> {
+    self.words.iter().find(|(known, _)| known == word).map(|(_, value)| *value)
+  }
 
-const FIRST_REGEX: &str = r"(?:(?<digit>\d)|(?<number>zero|one|two|three|four|five|six|seven|eight|nine))";
-const LAST_REGEX: &str = r".*(?:(?<digit>\d)|(?<number>zero|one|two|three|four|five|six|seven|eight|nine))";
+  // Longer words go first in the alternation, so "oneight" doesn't stop at "one" when both are
+  // words. Overlaps between words are handled by where the match starts: the first regex takes the
+  // leftmost start, and the greedy .* in the last regex backs off to the rightmost start, so
+  // "eightwo" reads as eight from the left and two from the right.
+  fn alternation(&self) -> String {
+    let mut words: Vec<&str> = self.words.iter().map(|(word, _)| word.as_str()).collect();
+    words.sort_by_key(|word| std::cmp::Reverse(word.len()));
+    words.into_iter().map(regex::escape).collect::<Vec<_>>().join("|")
+  }
+}
+
+// Both regexes for a vocabulary, compiled once and borrowed for every line. Regex is Send + Sync,
+// so a single Matcher can serve any number of threads.
+struct Matcher {
+  first_re: Regex,
+  last_re: Regex,
+  vocabulary: Vocabulary,
+}
 
-fn parse_number(regex: Box<Regex>, line: &str) -> Option<i32> {
+impl Matcher {
+  fn new(vocabulary: &Vocabulary) -> Matcher {
+    // An empty alternation would match everywhere, so without words there is no number group at all
+    let number = match vocabulary.words.is_empty() {
+      true => String::new(),
+      false => format!("|(?<number>{})", vocabulary.alternation()),
+    };
+    Matcher {
+      first_re: Regex::new(&format!(r"(?:(?<digit>[0-9]){})", number)).unwrap(),
+      last_re: Regex::new(&format!(r".*(?:(?<digit>[0-9]){})", number)).unwrap(),
+      vocabulary: vocabulary.clone(),
+    }
+  }
+
+  fn english() -> &'static Matcher {
+    static ENGLISH_MATCHER: OnceLock<Matcher> = OnceLock::new();
+    ENGLISH_MATCHER.get_or_init(|| Matcher::new(&Vocabulary::default()))
+  }
+}
+
+fn parse_number(regex: &Regex, vocabulary: &Vocabulary, line: &str) -> Option<i32> {
   match regex.captures(line) {
     Some(captures) => if let Some(digit) = captures.name("digit") {
       digit.as_str().parse().ok()
     } else if let Some(number) = captures.name("number") {
-      match number.as_str() {
-        "zero" => Some(0),
-        "one" => Some(1),
-        "two" => Some(2),
-        "three" => Some(3),
-        "four" => Some(4),
-        "five" => Some(5),
-        "six" => Some(6),
-        "seven" => Some(7),
-        "eight" => Some(8),
-        "nine" => Some(9),
-        _ => None
-      }
+      vocabulary.value(number.as_str())
     } else {
       None
     },
@@ -29,40 +109,397 @@ fn parse_number(regex: Box<Regex>, line: &str) -> Option<i32> {
   }
 }
 
-fn parse_line(first_re: Box<Regex>, last_re: Box<Regex>, line: &str) -> (i32, i32) {
-  let first_parsed = parse_number(first_re, &line).unwrap();
-  let last_parsed = parse_number(last_re, &line).unwrap_or(first_parsed);
-  (first_parsed, last_parsed)
+fn parse_line(matcher: &Matcher, line: &str) -> Option<(i32, i32)> {
+  let first_parsed = parse_number(&matcher.first_re, &matcher.vocabulary, line)?;
+  let last_parsed = parse_number(&matcher.last_re, &matcher.vocabulary, line).unwrap_or(first_parsed);
+  Some((first_parsed, last_parsed))
 }
 
-fn day_1(path: &str) -> i32 {
-  let first_re = Regex::new(FIRST_REGEX).unwrap();
-  let last_re = Regex::new(LAST_REGEX).unwrap();
-  let file = fs::read_to_string(path).expect("ok");
-  file
+// Part 1 reads only digits, part 2 reads the words of the vocabulary as well
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum Mode {
+  Digits(),
+  DigitsAndWords(),
+}
+
+impl Mode {
+  fn words<'a>(&self, vocabulary: &'a Vocabulary) -> &'a Vocabulary {
+    static NO_WORDS: Vocabulary = Vocabulary { words: Vec::new() };
+    match self {
+      Mode::Digits() => &NO_WORDS,
+      Mode::DigitsAndWords() => vocabulary,
+    }
+  }
+}
+
+// What to do with a line that has no digit or digit word at all. Skip and Zero give the same sum,
+// but --explain shows a zeroed line as counted and a skipped one as left out.
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum MissingDigits {
+  Skip(),
+  Zero(),
+  Fail(),
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum CalibrationError {
+  Io(String),
+  NoDigit { line: usize, text: String },
+  Overflow(u64),
+  // The benchmark's regexes and scanner disagree on the total
+  Mismatch { regexes: u64, scanner: u64 },
+}
+
+impl fmt::Display for CalibrationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CalibrationError::Io(message) => write!(f, "{}", message),
+      CalibrationError::NoDigit { line, text } => write!(f, "Line {} has no digit: {:?}", line, text),
+      CalibrationError::Overflow(total) => write!(f, "The total {} doesn't fit in an i32, try --threads", total),
+      CalibrationError::Mismatch { regexes, scanner } => write!(f, "The regexes give {} but the scanner gives {}", regexes, scanner),
+    }
+  }
+}
+
+// Sums in a u64, which no file that fits on a disk can overflow at 99 per line. first_line is the
+// number of the first line of text, for when text is only part of a file.
+fn calibrate_lines(text: &str, first_line: usize, missing_digits: MissingDigits, scan: impl Fn(&str) -> Option<(i32, i32)>) -> Result<u64, CalibrationError> {
+  text
     .lines()
-    .fold(0, |acc, line| {
-      let (first, second) = parse_line(Box::new(first_re), Box::new(last_re), &line);
-      acc + first * 10 + second
+    .enumerate()
+    .try_fold(0, |acc, (index, line)| {
+      match (scan(line), missing_digits) {
+        (Some((first, second)), _) => Ok(acc + (first * 10 + second) as u64),
+        (None, MissingDigits::Skip()) | (None, MissingDigits::Zero()) => Ok(acc),
+        (None, MissingDigits::Fail()) => Err(CalibrationError::NoDigit { line: first_line + index, text: line.into() }),
+      }
     })
 }
 
+// The original two-regex version, kept as the reference the scanner is checked and timed against
+fn calibrate_with_regexes(text: &str, matcher: &Matcher, missing_digits: MissingDigits) -> Result<u64, CalibrationError> {
+  calibrate_lines(text, 1, missing_digits, |line| parse_line(matcher, line))
+}
+
+fn calibrate(text: &str, scanner: &Scanner, missing_digits: MissingDigits) -> Result<u64, CalibrationError> {
+  calibrate_lines(text, 1, missing_digits, |line| scanner.scan(line))
+}
+
+fn io_error(path: &str) -> impl Fn(std::io::Error) -> CalibrationError + '_ {
+  move |error| CalibrationError::Io(format!("{}: {}", path, error))
+}
+
+fn read_input(path: &str) -> Result<String, CalibrationError> {
+  fs::read_to_string(path).map_err(io_error(path))
+}
+
+fn day_1(path: &str, mode: Mode, vocabulary: &Vocabulary, missing_digits: MissingDigits) -> Result<i32, CalibrationError> {
+  let total = calibrate(&read_input(path)?, &Scanner::new(mode.words(vocabulary)), missing_digits)?;
+  total.try_into().map_err(|_| CalibrationError::Overflow(total))
+}
+
+// Same answer as day_1, but streamed through a pool of threads so the file never has to fit in memory
+fn day_1_parallel(path: &str, mode: Mode, vocabulary: &Vocabulary, missing_digits: MissingDigits, threads: usize) -> Result<u64, CalibrationError> {
+  let file = fs::File::open(path).map_err(io_error(path))?;
+  parallel::calibrate(file, &Scanner::new(mode.words(vocabulary)), missing_digits, threads, parallel::BLOCK_SIZE).map_err(|error| match error {
+    CalibrationError::Io(message) => io_error(path)(std::io::Error::other(message)),
+    error => error,
+  })
+}
+
+// Repeats the input until it is at least the given number of megabytes and times both versions
+// at reading digits and words
+fn benchmark(path: &str, megabytes: usize, matcher: &Matcher, missing_digits: MissingDigits) -> Result<(), CalibrationError> {
+  let file = read_input(path)?;
+  let text = file.repeat((megabytes << 20) / file.len().max(1) + 1);
+  println!("{} lines, {} bytes", text.lines().count(), text.len());
+
+  let start = Instant::now();
+  let by_regexes = calibrate_with_regexes(&text, matcher, missing_digits)?;
+  let regex_time = start.elapsed();
+  let start = Instant::now();
+  let by_scanner = calibrate(&text, &Scanner::new(&matcher.vocabulary), missing_digits)?;
+  let scanner_time = start.elapsed();
+
+  let throughput = |seconds: f64| text.len() as f64 / seconds / (1 << 20) as f64;
+  println!("regexes: {} in {:?} ({:.1} MB/s)", by_regexes, regex_time, throughput(regex_time.as_secs_f64()));
+  println!("scanner: {} in {:?} ({:.1} MB/s)", by_scanner, scanner_time, throughput(scanner_time.as_secs_f64()));
+  if by_regexes != by_scanner {
+    return Err(CalibrationError::Mismatch { regexes: by_regexes, scanner: by_scanner })
+  }
+  Ok(())
+}
+
+enum ExplainFormat {
+  Text(),
+  Json(),
+}
+
+fn usage() -> ! {
+  eprintln!("Usage: day-1 [--mode digits|words] [--vocabulary FILE] [--missing-digits skip|zero|error] [--threads N] [--benchmark MEGABYTES] [--explain text|json]");
+  std::process::exit(1)
+}
+
+fn or_exit<T>(result: Result<T, CalibrationError>) -> T {
+  result.unwrap_or_else(|error| {
+    eprintln!("{}", error);
+    std::process::exit(1)
+  })
+}
+
+// Prints the answers for both modes, unless --mode picks one
 fn main() {
-  let r = day_1("input");
-  println!("{}", r);
+  let mut modes = vec![Mode::Digits(), Mode::DigitsAndWords()];
+  let mut custom_vocabulary = None;
+  let mut missing_digits = MissingDigits::Fail();
+  let mut benchmark_megabytes = None;
+  let mut threads = None;
+  let mut explain_format = None;
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    let value = args.next().unwrap_or_else(|| usage());
+    match arg.as_str() {
+      "--mode" => modes = match value.as_str() {
+        "digits" => vec![Mode::Digits()],
+        "words" => vec![Mode::DigitsAndWords()],
+        _ => usage(),
+      },
+      "--vocabulary" => custom_vocabulary = Some(Vocabulary::from_file(&value).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1)
+      })),
+      "--missing-digits" => missing_digits = match value.as_str() {
+        "skip" => MissingDigits::Skip(),
+        "zero" => MissingDigits::Zero(),
+        "error" => MissingDigits::Fail(),
+        _ => usage(),
+      },
+      "--threads" => threads = Some(value.parse().ok().filter(|threads| *threads > 0).unwrap_or_else(|| usage())),
+      "--benchmark" => benchmark_megabytes = Some(value.parse().unwrap_or_else(|_| usage())),
+      "--explain" => explain_format = match value.as_str() {
+        "text" => Some(ExplainFormat::Text()),
+        "json" => Some(ExplainFormat::Json()),
+        _ => usage(),
+      },
+      _ => usage(),
+    }
+  }
+  if let Some(megabytes) = benchmark_megabytes {
+    // Only the benchmark needs the regexes, to time the scanner against them
+    let custom_matcher = custom_vocabulary.as_ref().map(Matcher::new);
+    let matcher = custom_matcher.as_ref().unwrap_or_else(|| Matcher::english());
+    return or_exit(benchmark("input", megabytes, matcher, missing_digits))
+  }
+  let vocabulary = &custom_vocabulary.unwrap_or_default();
+  if let Some(format) = explain_format {
+    let file = or_exit(read_input("input"));
+    let mode = modes[modes.len() - 1];
+    for explanation in explain::explain(&file, &Scanner::new(mode.words(vocabulary)), missing_digits) {
+      match format {
+        ExplainFormat::Text() => println!("{}", explanation.to_text()),
+        ExplainFormat::Json() => println!("{}", explanation.to_json()),
+      }
+    }
+    return
+  }
+  for mode in modes {
+    match threads {
+      Some(threads) => println!("{}", or_exit(day_1_parallel("input", mode, vocabulary, missing_digits, threads))),
+      None => println!("{}", or_exit(day_1("input", mode, vocabulary, missing_digits))),
+    }
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  #[test]
+  fn it_reads_only_digits_in_digits_mode() {
+    let english = Vocabulary::default();
+    assert_eq!(day_1("test_input", Mode::Digits(), &english, MissingDigits::Fail()), Ok(142));
+    assert_eq!(day_1("test_input_2", Mode::Digits(), &english, MissingDigits::Fail()), Ok(11 + 79 + 53 + 11));
+
+    let french = Vocabulary::from_file("test_vocabulary").unwrap();
+    assert_eq!(
+      day_1("test_input_3", Mode::Digits(), &french, MissingDigits::Fail()),
+      Err(CalibrationError::NoDigit { line: 1, text: "huitxun".into() })
+    );
+    assert_eq!(day_1("test_input_3", Mode::Digits(), &french, MissingDigits::Skip()), Ok(33 + 99));
+    assert_eq!(day_1_parallel("test_input_2", Mode::Digits(), &english, MissingDigits::Fail(), 2), Ok(11 + 79 + 53 + 11));
+
+    let matcher = Matcher::new(Mode::Digits().words(&english));
+    assert_eq!(parse_line(&matcher, "two1nine"), Some((1, 1)));
+    assert_eq!(parse_line(&matcher, "eightwothree"), None);
+  }
+
   #[test]
   fn it_works_with_test_input() {
-    assert_eq!(day_1("test_input"), 142);
+    assert_eq!(day_1("test_input", Mode::DigitsAndWords(), &Vocabulary::default(), MissingDigits::Fail()), Ok(142));
   }
 
   #[test]
   fn it_works_with_test_input_2() {
-    assert_eq!(day_1("test_input_2"), 51 + 29 + 53 + 19);
+    assert_eq!(day_1("test_input_2", Mode::DigitsAndWords(), &Vocabulary::default(), MissingDigits::Fail()), Ok(51 + 29 + 53 + 19));
+  }
+
+  #[test]
+  fn it_reads_overlapping_words_from_both_ends() {
+    let parse = |line| parse_line(Matcher::english(), line);
+    assert_eq!(parse("eightwo"), Some((8, 2)));
+    assert_eq!(parse("oneight"), Some((1, 8)));
+    assert_eq!(parse("xtwonex"), Some((2, 1)));
+    assert_eq!(parse("sevenineightwone"), Some((7, 1)));
+    assert_eq!(parse("nineight7"), Some((9, 7)));
+    assert_eq!(parse("no digits"), None);
+  }
+
+  #[test]
+  fn it_works_with_a_custom_vocabulary() {
+    let vocabulary = Vocabulary::from_file("test_vocabulary").unwrap();
+    assert_eq!(vocabulary.value("huit"), Some(8));
+    assert_eq!(vocabulary.value("first"), Some(1));
+    assert_eq!(vocabulary.value("eight"), None);
+    assert_eq!(day_1("test_input_3", Mode::DigitsAndWords(), &vocabulary, MissingDigits::Fail()), Ok(81 + 12 + 13 + 99 + 44));
+
+    // "second" and "deux" overlap on the d, so each end gets its own 2
+    let matcher = Matcher::new(&vocabulary);
+    let parse = |line| parse_line(&matcher, line);
+    assert_eq!(parse("huitrois"), Some((8, 3)));
+    assert_eq!(parse("deuxun"), Some((2, 1)));
+    assert_eq!(parse("secondeux"), Some((2, 2)));
+  }
+
+  #[test]
+  fn it_rejects_bad_vocabularies() {
+    assert!(Vocabulary::parse("huit").unwrap_err().contains("Line 1"));
+    assert!(Vocabulary::parse("# French\nhuit 8\nneuf 10").unwrap_err().contains("Line 3"));
+    assert!(Vocabulary::parse("# nothing here\n\n").is_err());
+  }
+
+  #[test]
+  fn the_scanner_agrees_with_the_regexes() {
+    for vocabulary in [Vocabulary::default(), Vocabulary::from_file("test_vocabulary").unwrap()] {
+      let matcher = Matcher::new(&vocabulary);
+      let scanner = Scanner::new(&vocabulary);
+      let fragments = ["", "x", "7", "one", "eight", "two", "ne", "nin", "huit", "un", "deux", "second", "first", "zéro", "dozen-ish"];
+      for a in fragments {
+        for b in fragments {
+          for c in fragments {
+            let line = format!("{}{}{}", a, b, c);
+            let by_regexes = (parse_number(&matcher.first_re, &vocabulary, &line), parse_number(&matcher.last_re, &vocabulary, &line));
+            let by_scanner = scanner.scan(&line);
+            assert_eq!(by_scanner.map(|(first, _)| first), by_regexes.0, "first in {:?}", line);
+            assert_eq!(by_scanner.map(|(_, last)| last), by_regexes.1, "last in {:?}", line);
+          }
+        }
+      }
+    }
+
+    let input = fs::read_to_string("input").unwrap();
+    assert_eq!(
+      calibrate(&input, &Scanner::new(&Vocabulary::default()), MissingDigits::Fail()),
+      calibrate_with_regexes(&input, Matcher::english(), MissingDigits::Fail())
+    );
+  }
+
+  #[test]
+  fn it_explains_every_line() {
+    let text = "two1nine\nno digits here\n7";
+    let explanations = explain::explain(text, &Scanner::new(&Vocabulary::default()), MissingDigits::Skip());
+    assert_eq!(explanations.len(), 3);
+    assert_eq!(explanations[0].to_text(), r#"1: two1nine -> 29 (first "two" 0..3 word 2, last "nine" 4..8 word 9)"#);
+    assert_eq!(explanations[1].to_text(), "2: no digits here -> NO DIGIT");
+    assert_eq!(explanations[2].to_text(), r#"3: 7 -> 77 (first "7" 0..1 digit 7, last "7" 0..1 digit 7)"#);
+    assert_eq!(
+      explanations[0].to_json(),
+      r#"{"line":1,"text":"two1nine","first":{"start":0,"end":3,"kind":"word","value":2},"last":{"start":4,"end":8,"kind":"word","value":9},"value":29}"#
+    );
+    assert_eq!(explanations[1].to_json(), r#"{"line":2,"text":"no digits here","first":null,"last":null,"value":null}"#);
+
+    // Spans are in bytes, so they stay usable on lines with multibyte characters
+    let vocabulary = Vocabulary::from_file("test_vocabulary").unwrap();
+    let explanations = explain::explain("ézéro", &Scanner::new(&vocabulary), MissingDigits::Fail());
+    assert_eq!(explanations[0].to_text(), r#"1: ézéro -> 0 (first "zéro" 2..7 word 0, last "zéro" 2..7 word 0)"#);
+  }
+
+  #[test]
+  fn it_handles_lines_without_digits() {
+    let vocabulary = Vocabulary::default();
+    assert_eq!(day_1("test_input_4", Mode::DigitsAndWords(), &vocabulary, MissingDigits::Skip()), Ok(12 + 88));
+    assert_eq!(day_1("test_input_4", Mode::DigitsAndWords(), &vocabulary, MissingDigits::Zero()), Ok(12 + 88));
+    // The sums agree, so the difference only shows when explaining
+    let explanations = explain::explain("abc", &Scanner::new(&vocabulary), MissingDigits::Zero());
+    assert_eq!(explanations[0].value, Some(0));
+    assert_eq!(explanations[0].to_text(), "1: abc -> NO DIGIT, counted as 0");
+    assert_eq!(explain::explain("abc", &Scanner::new(&vocabulary), MissingDigits::Skip())[0].to_text(), "1: abc -> NO DIGIT");
+    let error = day_1("test_input_4", Mode::DigitsAndWords(), &vocabulary, MissingDigits::Fail()).unwrap_err();
+    assert_eq!(error, CalibrationError::NoDigit { line: 2, text: "".into() });
+    assert_eq!(error.to_string(), "Line 2 has no digit: \"\"");
+    assert_eq!(
+      calibrate("abc\nxyz", &Scanner::new(&vocabulary), MissingDigits::Fail()),
+      Err(CalibrationError::NoDigit { line: 1, text: "abc".into() })
+    );
+    assert!(matches!(day_1("no_such_file", Mode::DigitsAndWords(), &vocabulary, MissingDigits::Skip()), Err(CalibrationError::Io(_))));
+  }
+
+  #[test]
+  fn one_matcher_serves_many_threads() {
+    let input = fs::read_to_string("input").unwrap();
+    let lines: Vec<&str> = input.lines().collect();
+    let expected = calibrate_with_regexes(&input, Matcher::english(), MissingDigits::Fail()).unwrap();
+    let scanner = Scanner::new(&Vocabulary::default());
+    let (by_regexes, by_scanner): (Vec<u64>, Vec<u64>) = std::thread::scope(|scope| {
+      let handles: Vec<_> = lines
+        .chunks(lines.len() / 4 + 1)
+        .map(|chunk| scope.spawn(|| {
+          let text = chunk.join("\n");
+          (
+            calibrate_with_regexes(&text, Matcher::english(), MissingDigits::Fail()).unwrap(),
+            calibrate(&text, &scanner, MissingDigits::Fail()).unwrap(),
+          )
+        }))
+        .collect();
+      handles.into_iter().map(|handle| handle.join().unwrap()).unzip()
+    });
+    assert_eq!(by_regexes.iter().sum::<u64>(), expected);
+    assert_eq!(by_scanner.iter().sum::<u64>(), expected);
+  }
+
+  #[test]
+  fn the_parallel_sum_matches_the_sequential_fold() {
+    let vocabulary = Vocabulary::default();
+    let scanner = Scanner::new(&vocabulary);
+    for path in ["input", "test_input", "test_input_2", "test_input_4"] {
+      let text = fs::read_to_string(path).unwrap();
+      for missing_digits in [MissingDigits::Skip(), MissingDigits::Fail()] {
+        let sequential = calibrate(&text, &scanner, missing_digits);
+        // Blocks far shorter than a line as well as ones holding the whole file
+        for block_size in [1, 7, 100, 1 << 20] {
+          for threads in [1, 3, 8] {
+            let parallel = parallel::calibrate(text.as_bytes(), &scanner, missing_digits, threads, block_size);
+            assert_eq!(parallel, sequential, "{} in blocks of {} on {} threads", path, block_size, threads);
+          }
+        }
+      }
+    }
+    assert_eq!(day_1_parallel("input", Mode::DigitsAndWords(), &vocabulary, MissingDigits::Fail(), 4), Ok(day_1("input", Mode::DigitsAndWords(), &vocabulary, MissingDigits::Fail()).unwrap() as u64));
+
+    // Digit-free lines in several blocks, reported by the first one's line number
+    let text = "12\n\n34\nabc\n56\n".repeat(50);
+    assert_eq!(
+      parallel::calibrate(text.as_bytes(), &scanner, MissingDigits::Fail(), 4, 9),
+      Err(CalibrationError::NoDigit { line: 2, text: "".into() })
+    );
+    assert_eq!(parallel::calibrate(text.as_bytes(), &scanner, MissingDigits::Skip(), 4, 9), Ok(50 * (12 + 34 + 56)));
+    assert_eq!(parallel::calibrate(&b"1\n\xff\n"[..], &scanner, MissingDigits::Skip(), 2, 3), Err(CalibrationError::Io("Line 2 is not valid UTF-8".into())));
+  }
+
+  #[test]
+  fn sums_go_past_the_range_of_an_i32() {
+    let total = calibrate_lines("99\n99", 1, MissingDigits::Fail(), |_| Some((i32::MAX / 20, 9))).unwrap();
+    assert_eq!(total, 2 * (i32::MAX as u64 / 20 * 10 + 9));
+    assert!(i32::try_from(total).is_err());
+    assert_eq!(CalibrationError::Overflow(total).to_string(), "The total 2147483658 doesn't fit in an i32, try --threads");
   }
 }
@@ -0,0 +1,85 @@
+// Splits a stream into blocks that end on a line boundary and sums the blocks on worker threads.
+// The reader stays on the calling thread and the channel between it and the workers is bounded,
+// so only a few blocks are ever in memory at once.
+
+use std::io::Read;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use crate::{CalibrationError, MissingDigits, calibrate_lines};
+use crate::scanner::Scanner;
+
+pub const BLOCK_SIZE: usize = 4 << 20;
+
+struct Block {
+  first_line: usize,
+  text: String,
+}
+
+// Calls send with each block until the stream ends or send returns false
+fn read_blocks(mut reader: impl Read, block_size: usize, mut send: impl FnMut(Block) -> bool) -> Result<(), CalibrationError> {
+  let mut buffer = Vec::with_capacity(block_size);
+  let mut first_line = 1;
+  loop {
+    let read = reader.by_ref().take(block_size as u64).read_to_end(&mut buffer).map_err(|error| CalibrationError::Io(error.to_string()))?;
+    // A line longer than a block just keeps the block growing until its newline turns up
+    let end = match buffer.iter().rposition(|byte| *byte == b'\n') {
+      Some(_) if read == 0 => buffer.len(),
+      Some(newline) => newline + 1,
+      None if read == 0 => buffer.len(),
+      None => continue,
+    };
+    if end == 0 {
+      return Ok(())
+    }
+    let rest = buffer.split_off(end);
+    let lines = buffer.iter().filter(|byte| **byte == b'\n').count();
+    let text = String::from_utf8(std::mem::replace(&mut buffer, rest)).map_err(|error| {
+      let valid = &error.as_bytes()[.. error.utf8_error().valid_up_to()];
+      CalibrationError::Io(format!("Line {} is not valid UTF-8", first_line + valid.iter().filter(|byte| **byte == b'\n').count()))
+    })?;
+    if !send(Block { first_line, text }) {
+      return Ok(())
+    }
+    first_line += lines;
+  }
+}
+
+// Where several blocks fail, the error for the earliest line wins, as it would in a sequential fold.
+// Blocks leave the channel in order, so every block before a failing one still gets summed.
+pub fn calibrate(reader: impl Read, scanner: &Scanner, missing_digits: MissingDigits, threads: usize, block_size: usize) -> Result<u64, CalibrationError> {
+  let (sender, receiver) = mpsc::sync_channel::<Block>(threads);
+  // Each worker holds its own handle on the receiver, so once they have all stopped, on an error or
+  // otherwise, the receiver is gone and the next send fails instead of blocking forever
+  let receiver = Arc::new(Mutex::new(receiver));
+  thread::scope(|scope| {
+    let workers: Vec<_> = (0 .. threads)
+      .map(|_| {
+        let receiver = Arc::clone(&receiver);
+        scope.spawn(move || {
+          let mut total = 0;
+          loop {
+            let Ok(block) = receiver.lock().unwrap().recv() else {
+              return Ok(total)
+            };
+            total += calibrate_lines(&block.text, block.first_line, missing_digits, |line| scanner.scan(line))?;
+          }
+        })
+      })
+      .collect();
+    drop(receiver);
+    let reading = read_blocks(reader, block_size, |block| sender.send(block).is_ok());
+    drop(sender);
+
+    let results: Vec<Result<u64, CalibrationError>> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+    reading?;
+    let mut errors: Vec<CalibrationError> = results.iter().filter_map(|result| result.as_ref().err().cloned()).collect();
+    errors.sort_by_key(|error| match error {
+      CalibrationError::NoDigit { line, .. } => *line,
+      _ => 0,
+    });
+    match errors.into_iter().next() {
+      Some(error) => Err(error),
+      None => Ok(results.into_iter().map(|result| result.unwrap()).sum()),
+    }
+  })
+}
@@ -0,0 +1,69 @@
+// Finds the first and last digit or digit word of a line in a single pass, using one Aho-Corasick
+// automaton over the digits and every word of the vocabulary. The automaton reports overlapping
+// matches, so "eightwo" yields both eight and two, and the answer picks from those by start
+// position, exactly like the first and last regexes in main.rs do.
+
+use std::cmp::Reverse;
+use aho_corasick::{AhoCorasick, MatchKind};
+use serde::Serialize;
+use crate::Vocabulary;
+
+#[derive(Debug,Clone,Copy,PartialEq,Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+  Digit,
+  Word,
+}
+
+// A digit or digit word found in a line, with its byte span start..end
+#[derive(Debug,Clone,Copy,PartialEq,Serialize)]
+pub struct Token {
+  pub start: usize,
+  pub end: usize,
+  pub kind: TokenKind,
+  pub value: i32,
+}
+
+pub struct Scanner {
+  automaton: AhoCorasick,
+  values: Vec<i32>,
+}
+
+impl Scanner {
+  pub fn new(vocabulary: &Vocabulary) -> Scanner {
+    let digits = (0 ..= 9).map(|digit: i32| (digit.to_string(), digit));
+    let (patterns, values): (Vec<String>, Vec<i32>) = digits.chain(vocabulary.words.iter().cloned()).unzip();
+    let automaton = AhoCorasick::builder()
+      .match_kind(MatchKind::Standard)
+      .build(patterns)
+      .unwrap();
+    Scanner { automaton, values }
+  }
+
+  // The token starting first and the one starting last. Where two start at the same byte the
+  // longer one wins, which is what the regexes get from trying longer words first.
+  pub fn tokens(&self, line: &str) -> Option<(Token, Token)> {
+    let mut first: Option<Token> = None;
+    let mut last: Option<Token> = None;
+    for found in self.automaton.find_overlapping_iter(line) {
+      let pattern = found.pattern().as_usize();
+      let candidate = Token {
+        start: found.start(),
+        end: found.end(),
+        kind: if pattern < 10 { TokenKind::Digit } else { TokenKind::Word },
+        value: self.values[pattern],
+      };
+      if first.iter().all(|token| (candidate.start, Reverse(candidate.end)) < (token.start, Reverse(token.end))) {
+        first = Some(candidate);
+      }
+      if last.iter().all(|token| (candidate.start, candidate.end) > (token.start, token.end)) {
+        last = Some(candidate);
+      }
+    }
+    Some((first?, last?))
+  }
+
+  pub fn scan(&self, line: &str) -> Option<(i32, i32)> {
+    self.tokens(line).map(|(first, last)| (first.value, last.value))
+  }
+}
@@ -0,0 +1,93 @@
+// Treats each handful in a game as a sample drawn from a hidden bag without replacement, with the
+// cubes going back in the bag between handfuls, and looks for the bag that best explains them.
+
+use std::cmp::max;
+use std::collections::BTreeMap;
+use crate::Game;
+
+fn ln_choose(n: i32, k: i32) -> f64 {
+  if k < 0 || k > n {
+    return f64::NEG_INFINITY
+  }
+  (1 ..= k).map(|i| ((n - k + i) as f64 / i as f64).ln()).sum()
+}
+
+// Multivariate hypergeometric probability of every handful, multiplied together, as a logarithm.
+// A handful the bag can't produce gives -inf straight away, as -inf - -inf would be NaN.
+pub fn log_likelihood(game: &Game, bag: &BTreeMap<String, i32>) -> f64 {
+  let total = bag.values().sum();
+  game
+    .handfuls
+    .iter()
+    .map(|handful| {
+      if handful.cubes.iter().any(|(color, count)| *count > 0 && !bag.contains_key(color)) {
+        return f64::NEG_INFINITY
+      }
+      let drawn = handful.cubes.iter().map(|(_, count)| count).sum();
+      let ways: f64 = bag.iter().map(|(color, count)| ln_choose(*count, handful.count(color))).sum();
+      if drawn > total || ways == f64::NEG_INFINITY {
+        return f64::NEG_INFINITY
+      }
+      ways - ln_choose(total, drawn)
+    })
+    .sum()
+}
+
+pub fn likelihood(game: &Game, bag: &BTreeMap<String, i32>) -> f64 {
+  log_likelihood(game, bag).exp()
+}
+
+// "red=12..20,green=0..5", inclusive on both ends
+pub fn parse_bounds(spec: &str) -> Result<Vec<(String, i32, i32)>, String> {
+  spec
+    .split(',')
+    .map(str::trim)
+    .filter(|entry| !entry.is_empty())
+    .map(|entry| {
+      let bad_entry = || format!("Expected color=min..max, got {:?}", entry);
+      let (color, range) = entry.split_once('=').ok_or_else(bad_entry)?;
+      let (low, high) = range.split_once("..").ok_or_else(bad_entry)?;
+      let low = low.trim().parse().map_err(|_| bad_entry())?;
+      let high = high.trim().parse().map_err(|_| bad_entry())?;
+      Ok((color.trim().into(), low, high))
+    })
+    .collect()
+}
+
+// Tries every bag within bounds, skipping counts below what a single handful already shows.
+// Colors the game draws but the bounds leave out make every bag impossible, so that gives None.
+pub fn max_likelihood_bag(game: &Game, bounds: &[(String, i32, i32)]) -> Option<(BTreeMap<String, i32>, f64)> {
+  let ranges: Vec<(&str, i32, i32)> = bounds
+    .iter()
+    .map(|(color, low, high)| {
+      let seen = game.handfuls.iter().map(|handful| handful.count(color)).max().unwrap_or(0);
+      (color.as_str(), max(*low, seen), *high)
+    })
+    .collect();
+
+  let mut best: Option<(BTreeMap<String, i32>, f64)> = None;
+  let mut counts: Vec<i32> = ranges.iter().map(|(_, low, _)| *low).collect();
+  if ranges.iter().any(|(_, low, high)| low > high) {
+    return None
+  }
+  loop {
+    let bag: BTreeMap<String, i32> = ranges.iter().zip(&counts).map(|((color, _, _), count)| (color.to_string(), *count)).collect();
+    let candidate = log_likelihood(game, &bag);
+    let better = match &best {
+      Some((_, best)) => candidate > *best,
+      None => candidate > f64::NEG_INFINITY,
+    };
+    if better {
+      best = Some((bag, candidate));
+    }
+
+    // Count up like an odometer over the ranges
+    let Some(position) = (0 .. counts.len()).find(|position| counts[*position] < ranges[*position].2) else {
+      break best
+    };
+    counts[position] += 1;
+    for (count, (_, low, _)) in counts.iter_mut().zip(&ranges).take(position) {
+      *count = *low;
+    }
+  }
+}
@@ -1,92 +1,527 @@
+mod estimate;
+mod query;
+mod report;
+
 use std::fs;
 use std::cmp::max;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
 use regex::Regex;
+use indexmap::IndexMap;
+use query::{Aggregate, Expr, Op};
 
 const UNIVERSAL_ERROR_MESSAGE: &str = "Something went wrong. Help!";
 
-#[derive(Debug,Default)]
+// Cube counts by color, kept in the order they were listed so games format back as they were read
+#[derive(Debug,Default,Clone,PartialEq)]
 struct Handful {
-  red: i32,
-  green: i32,
-  blue: i32,
+  cubes: IndexMap<String, i32>,
+}
+
+impl Handful {
+  fn count(&self, color: &str) -> i32 {
+    self.cubes.get(color).copied().unwrap_or(0)
+  }
+
+  // A color listed twice keeps its first position and the last count
+  fn insert(&mut self, color: &str, count: i32) {
+    self.cubes.insert(color.into(), count);
+  }
+
+  fn colors(&self) -> impl Iterator<Item = &str> {
+    self.cubes.keys().map(String::as_str)
+  }
 }
 
-#[derive(Debug)]
+#[derive(Debug,PartialEq)]
 struct Game {
   id: i32,
   handfuls: Vec<Handful>,
 }
 
+impl Game {
+  fn colors(&self) -> BTreeSet<&str> {
+    self.handfuls.iter().flat_map(|handful| handful.colors()).collect()
+  }
+}
+
+// Writes games back out as "Game 1: 3 blue, 4 red; 1 red, 2 green", the inverse of parse_line
+impl std::fmt::Display for Handful {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let cubes: Vec<String> = self.cubes.iter().map(|(color, count)| format!("{} {}", count, color)).collect();
+    write!(f, "{}", cubes.join(", "))
+  }
+}
+
+impl std::fmt::Display for Game {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let handfuls: Vec<String> = self.handfuls.iter().map(Handful::to_string).collect();
+    write!(f, "Game {}: {}", self.id, handfuls.join("; "))
+  }
+}
+
 fn parse_line(line: &str) -> Game {
-  let re = Regex::new(r"Game (\d+):\s+(.*)").expect(UNIVERSAL_ERROR_MESSAGE);
-  let (_, [id_str, handfuls_str]) = re.captures(line).unwrap().extract();
+  static GAME_RE: OnceLock<Regex> = OnceLock::new();
+  static CUBES_RE: OnceLock<Regex> = OnceLock::new();
+  let game_re = GAME_RE.get_or_init(|| Regex::new(r"Game (\d+):\s+(.*)").expect(UNIVERSAL_ERROR_MESSAGE));
+  let cubes_re = CUBES_RE.get_or_init(|| Regex::new(r"(\d+) (\w+)").expect(UNIVERSAL_ERROR_MESSAGE));
+
+  let (_, [id_str, handfuls_str]) = game_re.captures(line).expect(UNIVERSAL_ERROR_MESSAGE).extract();
   let handfuls: Vec<Handful> = handfuls_str.split(';').map(|handful| {
-    Regex::new(r"(\d+) (\w+)")
-      .expect(UNIVERSAL_ERROR_MESSAGE)
+    cubes_re
       .captures_iter(handful)
-      .fold(Handful::default(), |result, captures| {
+      .fold(Handful::default(), |mut result, captures| {
         let (_, [count_str, color]) = captures.extract();
-        match color {
-          "red" => Handful { red: count_str.parse().expect(UNIVERSAL_ERROR_MESSAGE), ..result },
-          "green" => Handful { green: count_str.parse().expect(UNIVERSAL_ERROR_MESSAGE), ..result },
-          "blue" => Handful { blue: count_str.parse().expect(UNIVERSAL_ERROR_MESSAGE), ..result },
-          other_string => panic!("I have no idea what color this is: {}", other_string),
-        }
+        result.insert(color, count_str.parse().expect(UNIVERSAL_ERROR_MESSAGE));
+        result
       })
   }).collect();
 
-  return Game {
-    id: id_str.parse().unwrap(),
-    handfuls: handfuls.into(),
+  Game {
+    id: id_str.parse().expect(UNIVERSAL_ERROR_MESSAGE),
+    handfuls,
   }
 }
 
-fn day_2(input: &str) -> i32 {
+fn parse_file(input: &str) -> Vec<Game> {
   let file = fs::read_to_string(input).expect(UNIVERSAL_ERROR_MESSAGE);
-  file
+  file.lines().map(parse_line).collect()
+}
+
+enum UnknownColors {
+  Infeasible(),
+  Allowed(),
+}
+
+struct Bag {
+  limits: Vec<(String, i32)>,
+  unknown_colors: UnknownColors,
+}
+
+impl Default for Bag {
+  fn default() -> Self {
+    Bag {
+      limits: vec![("red".into(), 12), ("green".into(), 13), ("blue".into(), 14)],
+      unknown_colors: UnknownColors::Infeasible(),
+    }
+  }
+}
+
+impl Bag {
+  fn limit(&self, color: &str) -> Option<i32> {
+    self.limits.iter().find(|(bag_color, _)| bag_color == color).map(|(_, limit)| *limit)
+  }
+
+  // all(red <= 12 and green <= 13 and blue <= 14 and others(red, green, blue) == 0)
+  fn query(&self) -> Expr {
+    let mut conditions: Vec<Expr> = self
+      .limits
+      .iter()
+      .map(|(color, limit)| Expr::Binary(Op::Le(), Box::new(Expr::Color(color.clone())), Box::new(Expr::Number((*limit).into()))))
+      .collect();
+    if let UnknownColors::Infeasible() = self.unknown_colors {
+      let colors = self.limits.iter().map(|(color, _)| color.clone()).collect();
+      conditions.push(Expr::Binary(Op::Eq(), Box::new(Expr::Others(colors)), Box::new(Expr::Number(0))));
+    }
+    let condition = conditions
+      .into_iter()
+      .reduce(|left, right| Expr::Binary(Op::And(), Box::new(left), Box::new(right)))
+      .unwrap_or(Expr::Bool(true));
+    Expr::Aggregate(Aggregate::All(), Box::new(condition))
+  }
+}
+
+// "red=12,green=13,blue=14", or the same one per line with # comments, as in a config file
+fn parse_bag(spec: &str, unknown_colors: UnknownColors) -> Result<Bag, String> {
+  let limits = spec
     .lines()
-    .map(|line| parse_line(line))
-    .fold(0, |result, game| 
-      result + if game.handfuls.into_iter().all(|handful| handful.red < 13 && handful.green < 14 && handful.blue < 15) { game.id } else { 0 }
-    )
+    .map(|line| line.split('#').next().unwrap_or_default())
+    .flat_map(|line| line.split(','))
+    .map(str::trim)
+    .filter(|entry| !entry.is_empty())
+    .map(|entry| {
+      let (color, limit) = entry.split_once('=').ok_or(format!("Expected color=count, got {:?}", entry))?;
+      let limit = limit.trim().parse().map_err(|_| format!("Bad count for {}: {:?}", color.trim(), limit.trim()))?;
+      Ok((color.trim().into(), limit))
+    })
+    .collect::<Result<Vec<(String, i32)>, String>>()?;
+  Ok(Bag { limits, unknown_colors })
+}
+
+#[derive(Debug,PartialEq)]
+struct Violation {
+  game_id: i32,
+  handful: usize,
+  color: String,
+  count: i32,
+  // None when the color isn't in the bag at all
+  limit: Option<i32>,
 }
 
-fn game_power(game: Game) -> i32 {
-  let handful = game
+impl std::fmt::Display for Violation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Game {}, handful {}: {} {} ", self.game_id, self.handful + 1, self.count, self.color)?;
+    match self.limit {
+      Some(limit) => write!(f, "exceeds the limit of {}", limit),
+      None => write!(f, "is not in the bag"),
+    }
+  }
+}
+
+fn violations(game: &Game, bag: &Bag) -> Vec<Violation> {
+  game
     .handfuls
     .iter()
-    .fold(
-      Handful::default(),|Handful { red, green, blue }, handful|
-        Handful { red: max(handful.red, red), green: max(handful.green, green), blue: max(handful.blue, blue) }
-    );
-  handful.red * handful.green * handful.blue
+    .enumerate()
+    .flat_map(|(handful, cubes)| cubes.cubes.iter().map(move |(color, count)| (handful, color, *count)))
+    .filter_map(|(handful, color, count)| {
+      let limit = bag.limit(color);
+      let feasible = match (limit, &bag.unknown_colors) {
+        (Some(limit), _) => count <= limit,
+        (None, UnknownColors::Allowed()) => true,
+        (None, UnknownColors::Infeasible()) => count == 0,
+      };
+      (!feasible).then(|| Violation { game_id: game.id, handful, color: color.clone(), count, limit })
+    })
+    .collect()
+}
+
+struct Feasibility {
+  id_sum: i32,
+  violations: Vec<Violation>,
+}
+
+fn day_2(input: &str, bag: &Bag) -> Feasibility {
+  let games = parse_file(input);
+  let id_sum = query::total(&games, &bag.query(), &Expr::Id()).expect(UNIVERSAL_ERROR_MESSAGE);
+  Feasibility {
+    id_sum: id_sum.try_into().expect(UNIVERSAL_ERROR_MESSAGE),
+    violations: games.iter().flat_map(|game| violations(game, bag)).collect(),
+  }
+}
+
+const BASE_COLORS: [&str; 3] = ["red", "green", "blue"];
+
+// The fewest cubes of each color the game needs. Red, green and blue are always there, as zero if
+// the game never draws them, so three color games keep their power. Colors other games draw don't
+// count.
+fn minimum_bag(game: &Game) -> BTreeMap<String, i32> {
+  BASE_COLORS
+    .into_iter()
+    .chain(game.colors())
+    .map(|color| (color.into(), game.handfuls.iter().fold(0, |result, handful| max(result, handful.count(color)))))
+    .collect()
+}
+
+fn game_power(game: &Game) -> i32 {
+  minimum_bag(game).values().product()
 }
 
 fn day_2_2(input: &str) -> i32 {
-  let file = fs::read_to_string(input).expect(UNIVERSAL_ERROR_MESSAGE);
-  file
-    .lines()
-    .map(|line| parse_line(line))
-    .map(|game| game_power(game))
-    .fold(0, |sum, game_power| sum + game_power)
+  let power_sum = query::total(&parse_file(input), &Expr::Bool(true), &Expr::Power()).expect(UNIVERSAL_ERROR_MESSAGE);
+  power_sum.try_into().expect(UNIVERSAL_ERROR_MESSAGE)
+}
+
+enum ReportFormat {
+  Table(),
+  Json(),
+}
+
+struct Options {
+  bag: Bag,
+  show_violations: bool,
+  report: Option<ReportFormat>,
+  estimate: Option<Vec<(String, i32, i32)>>,
+  query: Option<Expr>,
+  sum: Option<Expr>,
+  show_records: bool,
+}
+
+// --bag red=12,green=13,blue=14 | --bag-file PATH, --unknown-colors infeasible|allowed, --violations,
+// --report table|json, --estimate red=12..20,green=13..20,blue=14..20,
+// --query "any(green > 10)" [--records] [--sum power]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+  let mut bag_spec = None;
+  let mut unknown_colors = UnknownColors::Infeasible();
+  let mut show_violations = false;
+  let mut report = None;
+  let mut estimate = None;
+  let mut query = None;
+  let mut sum = None;
+  let mut show_records = false;
+  while let Some(arg) = args.next() {
+    let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+    match arg.as_str() {
+      "--bag" => bag_spec = Some(value()?),
+      "--bag-file" => {
+        let path = value()?;
+        bag_spec = Some(fs::read_to_string(&path).map_err(|error| format!("{}: {}", path, error))?);
+      },
+      "--unknown-colors" => unknown_colors = match value()?.as_str() {
+        "infeasible" => UnknownColors::Infeasible(),
+        "allowed" => UnknownColors::Allowed(),
+        other => return Err(format!("Unknown colors are either infeasible or allowed, not {:?}", other)),
+      },
+      "--violations" => show_violations = true,
+      "--report" => report = match value()?.as_str() {
+        "table" => Some(ReportFormat::Table()),
+        "json" => Some(ReportFormat::Json()),
+        other => return Err(format!("Reports come as table or json, not {:?}", other)),
+      },
+      "--estimate" => estimate = Some(estimate::parse_bounds(&value()?)?),
+      "--query" => query = Some(query::parse(&value()?)?),
+      "--sum" => sum = Some(query::parse(&value()?)?),
+      "--records" => show_records = true,
+      other => return Err(format!("Unknown argument {:?}", other)),
+    }
+  }
+  let bag = match bag_spec {
+    Some(spec) => parse_bag(&spec, unknown_colors)?,
+    None => Bag { unknown_colors, ..Bag::default() },
+  };
+  Ok(Options { bag, show_violations, report, estimate, query, sum, show_records })
 }
 
 fn main() {
-  println!("{}", day_2("input"));
+  let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|error| {
+    eprintln!("{}", error);
+    std::process::exit(2)
+  });
+  if let Some(format) = options.report {
+    let report = report::report(&parse_file("input"));
+    match format {
+      ReportFormat::Table() => print!("{}", report.to_table()),
+      ReportFormat::Json() => println!("{}", serde_json::to_string_pretty(&report).expect(UNIVERSAL_ERROR_MESSAGE)),
+    }
+    return
+  }
+  if let Some(bounds) = options.estimate {
+    for game in parse_file("input") {
+      match estimate::max_likelihood_bag(&game, &bounds) {
+        Some((bag, _)) => {
+          let likelihood = estimate::likelihood(&game, &bag);
+          let bag: Vec<String> = bag.iter().map(|(color, count)| format!("{}={}", color, count)).collect();
+          println!("Game {}: {} with likelihood {:.3e}", game.id, bag.join(","), likelihood);
+        },
+        None => println!("Game {}: no bag within bounds could produce it", game.id),
+      }
+    }
+    return
+  }
+  if options.query.is_some() || options.sum.is_some() {
+    let games = parse_file("input");
+    let filter = options.query.unwrap_or(Expr::Bool(true));
+    let result = match options.sum {
+      Some(value) => query::total(&games, &filter, &value).map(|total| println!("{}", total)),
+      None => {
+        games.iter().try_for_each(|game| {
+          if query::matches(&filter, game)? {
+            if options.show_records { println!("{}", game) } else { println!("{}", game.id) }
+          }
+          Ok(())
+        })
+      },
+    };
+    if let Err(error) = result {
+      eprintln!("{}", error);
+      std::process::exit(1)
+    }
+    return
+  }
+  let feasibility = day_2("input", &options.bag);
+  println!("{}", feasibility.id_sum);
+  if options.show_violations {
+    for violation in &feasibility.violations {
+      eprintln!("{}", violation);
+    }
+  }
   println!("{}", day_2_2("input"));
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use proptest::prelude::*;
 
   #[test]
   fn it_works_with_test_input() {
-    assert_eq!(day_2("test_input"), 8);
+    assert_eq!(day_2("test_input", &Bag::default()).id_sum, 8);
+  }
+
+  #[test]
+  fn it_reports_violations() {
+    let violations = day_2("test_input", &Bag::default()).violations;
+    assert_eq!(violations.len(), 3);
+    assert_eq!(violations[0], Violation { game_id: 3, handful: 0, color: "red".into(), count: 20, limit: Some(12) });
+    assert_eq!(violations[0].to_string(), "Game 3, handful 1: 20 red exceeds the limit of 12");
+  }
+
+  #[test]
+  fn it_takes_a_bag_specification() {
+    let bag = parse_bag("red=20, green=13\n# no blue here\n", UnknownColors::Infeasible()).unwrap();
+    let violations = day_2("test_input", &bag).violations;
+    assert!(violations.iter().all(|violation| violation.color == "blue"));
+    assert_eq!(violations[0].to_string(), "Game 1, handful 1: 3 blue is not in the bag");
+
+    let bag = parse_bag("red=20,green=13", UnknownColors::Allowed()).unwrap();
+    assert_eq!(day_2("test_input", &bag).id_sum, 1 + 2 + 3 + 4 + 5);
+
+    assert!(parse_bag("red:12", UnknownColors::Allowed()).is_err());
+    assert!(parse_bag("red=many", UnknownColors::Allowed()).is_err());
   }
 
   #[test]
   fn it_2_works_with_test_input() {
     assert_eq!(day_2_2("test_input"), 2286);
   }
+
+  #[test]
+  fn it_handles_other_colors() {
+    let game = parse_line("Game 7: 3 blue, 2 purple; 1 purple, 4 teal");
+    assert_eq!(game.handfuls[0].count("purple"), 2);
+    assert_eq!(game.handfuls[1].count("teal"), 4);
+    assert_eq!(game.handfuls[1].count("blue"), 0);
+    // No red or green drawn, so power is 0 whatever the other colors
+    assert_eq!(game_power(&game), 0);
+    let game = parse_line("Game 8: 1 red, 1 green, 3 blue, 2 purple; 1 purple, 4 teal");
+    assert_eq!(game_power(&game), 3 * 2 * 4);
+  }
+
+  #[test]
+  fn it_counts_missing_base_colors_as_zero() {
+    let game = parse_line("Game 1: 3 red; 2 blue");
+    assert_eq!(game_power(&game), 0);
+    let report = report::report(&[game]);
+    let stats = &report.games[0];
+    assert_eq!(stats.minimum_bag, BTreeMap::from([("blue".into(), 2), ("green".into(), 0), ("red".into(), 3)]));
+    assert_eq!(stats.power, 0);
+    assert_eq!(stats.least_constrained.as_deref(), Some("green"));
+  }
+
+  #[test]
+  fn it_reports_game_statistics() {
+    let report = report::report(&parse_file("test_input"));
+    let game = &report.games[0];
+    assert_eq!(game.minimum_bag, BTreeMap::from([("blue".into(), 6), ("green".into(), 2), ("red".into(), 4)]));
+    assert_eq!(game.power, 48);
+    assert_eq!(game.totals, BTreeMap::from([("blue".into(), 9), ("green".into(), 4), ("red".into(), 5)]));
+    assert_eq!(game.most_constrained.as_deref(), Some("blue"));
+    assert_eq!(game.least_constrained.as_deref(), Some("green"));
+
+    let distribution = report.power_distribution.as_ref().unwrap();
+    assert_eq!((distribution.min, distribution.max, distribution.median), (12, 1560, 48.0));
+    assert_eq!(distribution.mean, 2286.0 / 5.0);
+    assert!(report.shared_minimum_bags.is_empty());
+
+    let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["games"][2]["power"], 1560);
+    assert!(report.to_table().starts_with("game  power  minimum bag"));
+  }
+
+  #[test]
+  fn colors_from_other_games_leave_power_alone() {
+    // test_input with a game 6 drawing a purple cube as well
+    assert_eq!(day_2_2("test_input_colors"), 2286 + 1);
+    let report = report::report(&parse_file("test_input_colors"));
+    let powers: Vec<i32> = report.games.iter().map(|game| game.power).collect();
+    assert_eq!(powers, vec![48, 12, 1560, 630, 36, 1]);
+    assert!(!report.games[0].minimum_bag.contains_key("purple"));
+    assert_eq!(report.games[5].minimum_bag["purple"], 1);
+  }
+
+  #[test]
+  fn it_computes_handful_likelihoods() {
+    let game = parse_line("Game 1: 1 red, 1 blue");
+    let bag = BTreeMap::from([("red".into(), 2), ("blue".into(), 2)]);
+    assert!((estimate::likelihood(&game, &bag) - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(estimate::likelihood(&game, &BTreeMap::from([("red".into(), 5)])), 0.0);
+    // More cubes drawn than the bag holds
+    assert_eq!(estimate::likelihood(&parse_line("Game 1: 1 red"), &BTreeMap::from([("red".into(), 0)])), 0.0);
+    assert_eq!(estimate::likelihood(&parse_line("Game 1: 3 red"), &BTreeMap::from([("red".into(), 2)])), 0.0);
+
+    let game = parse_line("Game 2: 2 red; 1 red, 1 blue");
+    // Two handfuls: C(r,2)/C(r+b,2) * r*b/C(r+b,2)
+    let bag = BTreeMap::from([("red".into(), 3), ("blue".into(), 1)]);
+    assert!((estimate::likelihood(&game, &bag) - (3.0 / 6.0) * (3.0 / 6.0)).abs() < 1e-9);
+  }
+
+  #[test]
+  fn it_finds_the_most_likely_bag() {
+    let bounds = estimate::parse_bounds("red=0..5, blue=0..5").unwrap();
+    let (bag, _) = estimate::max_likelihood_bag(&parse_line("Game 1: 1 red"), &bounds).unwrap();
+    assert_eq!(bag, BTreeMap::from([("red".into(), 1), ("blue".into(), 0)]));
+
+    let (bag, _) = estimate::max_likelihood_bag(&parse_line("Game 2: 1 red, 1 blue; 2 red"), &bounds).unwrap();
+    assert_eq!(bag, BTreeMap::from([("red".into(), 3), ("blue".into(), 1)]));
+
+    assert!(estimate::max_likelihood_bag(&parse_line("Game 3: 1 green"), &bounds).is_none());
+    assert!(estimate::max_likelihood_bag(&parse_line("Game 4: 9 red"), &bounds).is_none());
+    assert!(estimate::parse_bounds("red=5").is_err());
+  }
+
+  #[test]
+  fn it_formats_input_files_back_unchanged() {
+    for path in ["test_input", "input"] {
+      for line in fs::read_to_string(path).unwrap().lines() {
+        assert_eq!(parse_line(line).to_string(), line.trim());
+      }
+    }
+  }
+
+  fn handful_strategy() -> impl Strategy<Value = Handful> {
+    prop::collection::vec(("[a-z]{1,8}", 0 .. 100), 1 .. 5)
+      .prop_map(|cubes| cubes.iter().fold(Handful::default(), |mut handful, (color, count)| {
+        if handful.colors().all(|seen| seen != color) {
+          handful.insert(color, *count);
+        }
+        handful
+      }))
+  }
+
+  fn game_strategy() -> impl Strategy<Value = Game> {
+    (0 .. 10000, prop::collection::vec(handful_strategy(), 1 .. 8))
+      .prop_map(|(id, handfuls)| Game { id, handfuls })
+  }
+
+  proptest! {
+    #[test]
+    fn formatting_then_parsing_gives_the_same_game(game in game_strategy()) {
+      // Handful equality ignores color order, so compare the text too
+      let parsed = parse_line(&game.to_string());
+      prop_assert_eq!(parsed.to_string(), game.to_string());
+      prop_assert_eq!(parsed, game);
+    }
+  }
+
+  fn matching_ids(source: &str) -> Vec<i32> {
+    let expr = query::parse(source).unwrap();
+    parse_file("test_input").iter().filter(|game| query::matches(&expr, game).unwrap()).map(|game| game.id).collect()
+  }
+
+  #[test]
+  fn it_filters_games_with_queries() {
+    assert_eq!(matching_ids("any(green > 10)"), vec![3]);
+    assert_eq!(matching_ids("max(blue) >= max(red)"), vec![1, 2, 4]);
+    assert_eq!(matching_ids("max(blue) ≥ max(red) and not id = 4"), vec![1, 2]);
+    assert_eq!(matching_ids("all(red + green + blue <= 10) or handfuls == 2"), vec![1, 2, 5]);
+    assert_eq!(matching_ids("sum(red) * 2 > 20 and min(others(red)) > 0"), vec![3, 4]);
+    assert_eq!(matching_ids("power == 48 or (power > 1000)"), vec![1, 3]);
+
+    let games = parse_file("test_input");
+    assert_eq!(query::total(&games, &query::parse("all(red <= 12 and green <= 13 and blue <= 14)").unwrap(), &Expr::Id()), Ok(8));
+    assert_eq!(query::total(&games, &Expr::Bool(true), &query::parse("max(red) * max(green) * max(blue)").unwrap()), Ok(2286));
+  }
+
+  #[test]
+  fn it_rejects_bad_queries() {
+    assert!(query::parse("green > 10").unwrap_err().contains("needs any/all"));
+    assert!(query::parse("any(max(green) > 1)").unwrap_err().contains("nested"));
+    assert!(query::parse("any(green > ").is_err());
+    assert!(query::parse("any(green > 1))").is_err());
+    assert!(query::parse("any(green # 1)").is_err());
+
+    let games = parse_file("test_input");
+    assert!(query::total(&games, &query::parse("id").unwrap(), &Expr::Id()).is_err());
+    assert!(query::total(&games, &Expr::Bool(true), &query::parse("id > 1").unwrap()).is_err());
+  }
 }
@@ -0,0 +1,335 @@
+// A small expression language for picking out games, for instance
+//   any(green > 10)
+//   max(blue) >= max(red) and not all(red <= 12)
+//   all(red <= 12 and green <= 13 and blue <= 14 and others(red, green, blue) == 0)
+//
+// Game level values are id, handfuls (how many) and power. Color names count cubes of that color
+// in one handful, so they only make sense inside any/all, which test every handful, or inside
+// max/min/sum, which fold a number over the handfuls. others(red, green) counts the cubes of every
+// color not listed. Numbers combine with + - * and compare with < <= > >= == != (or ≤ ≥), and
+// conditions combine with and, or, not.
+
+use crate::{Game, Handful, game_power};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Aggregate {
+  Any(),
+  All(),
+  Max(),
+  Min(),
+  Sum(),
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Op {
+  Add(),
+  Sub(),
+  Mul(),
+  Lt(),
+  Le(),
+  Gt(),
+  Ge(),
+  Eq(),
+  Ne(),
+  And(),
+  Or(),
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Expr {
+  Number(i64),
+  Bool(bool),
+  Id(),
+  Handfuls(),
+  Power(),
+  Color(String),
+  Others(Vec<String>),
+  Not(Box<Expr>),
+  Binary(Op, Box<Expr>, Box<Expr>),
+  Aggregate(Aggregate, Box<Expr>),
+}
+
+#[derive(Debug,PartialEq)]
+pub enum Value {
+  Number(i64),
+  Bool(bool),
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Token {
+  Number(i64),
+  Ident(String),
+  Symbol(&'static str),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+  const SYMBOLS: [&str; 16] = ["<=", ">=", "==", "!=", "≤", "≥", "<", ">", "=", "(", ")", ",", "+", "-", "*", "!"];
+  let mut tokens = vec![];
+  let mut rest = source.trim_start();
+  while let Some(c) = rest.chars().next() {
+    if c.is_ascii_digit() {
+      let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+      tokens.push(Token::Number(rest[.. end].parse().map_err(|_| format!("Number too large: {}", &rest[.. end]))?));
+      rest = &rest[end ..];
+    } else if c.is_alphabetic() || c == '_' {
+      let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+      tokens.push(Token::Ident(rest[.. end].into()));
+      rest = &rest[end ..];
+    } else {
+      let symbol = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)).ok_or(format!("Unexpected {:?}", c))?;
+      tokens.push(Token::Symbol(symbol));
+      rest = &rest[symbol.len() ..];
+    }
+    rest = rest.trim_start();
+  }
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  position: usize,
+  // Inside any/all/max/min/sum, where color names refer to a single handful
+  in_handful: bool,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position)
+  }
+
+  fn accept_symbol(&mut self, symbols: &[&'static str]) -> Option<&'static str> {
+    match self.peek().cloned() {
+      Some(Token::Symbol(symbol)) if symbols.contains(&symbol) => {
+        self.position += 1;
+        Some(symbol)
+      },
+      _ => None,
+    }
+  }
+
+  fn accept_keyword(&mut self, keyword: &str) -> bool {
+    let found = matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword);
+    if found {
+      self.position += 1;
+    }
+    found
+  }
+
+  fn expect_symbol(&mut self, symbol: &'static str) -> Result<(), String> {
+    self.accept_symbol(&[symbol]).map(|_| ()).ok_or(format!("Expected {:?} at token {}", symbol, self.position + 1))
+  }
+
+  fn or(&mut self) -> Result<Expr, String> {
+    let mut left = self.and()?;
+    while self.accept_keyword("or") {
+      left = Expr::Binary(Op::Or(), Box::new(left), Box::new(self.and()?));
+    }
+    Ok(left)
+  }
+
+  fn and(&mut self) -> Result<Expr, String> {
+    let mut left = self.not()?;
+    while self.accept_keyword("and") {
+      left = Expr::Binary(Op::And(), Box::new(left), Box::new(self.not()?));
+    }
+    Ok(left)
+  }
+
+  fn not(&mut self) -> Result<Expr, String> {
+    if self.accept_keyword("not") || self.accept_symbol(&["!"]).is_some() {
+      return Ok(Expr::Not(Box::new(self.not()?)))
+    }
+    self.comparison()
+  }
+
+  fn comparison(&mut self) -> Result<Expr, String> {
+    let left = self.sum()?;
+    let op = match self.accept_symbol(&["<=", ">=", "==", "!=", "≤", "≥", "<", ">", "="]) {
+      Some("<") => Op::Lt(),
+      Some("<=") | Some("≤") => Op::Le(),
+      Some(">") => Op::Gt(),
+      Some(">=") | Some("≥") => Op::Ge(),
+      Some("==") | Some("=") => Op::Eq(),
+      Some("!=") => Op::Ne(),
+      _ => return Ok(left),
+    };
+    Ok(Expr::Binary(op, Box::new(left), Box::new(self.sum()?)))
+  }
+
+  fn sum(&mut self) -> Result<Expr, String> {
+    let mut left = self.product()?;
+    while let Some(symbol) = self.accept_symbol(&["+", "-"]) {
+      let op = if symbol == "+" { Op::Add() } else { Op::Sub() };
+      left = Expr::Binary(op, Box::new(left), Box::new(self.product()?));
+    }
+    Ok(left)
+  }
+
+  fn product(&mut self) -> Result<Expr, String> {
+    let mut left = self.atom()?;
+    while self.accept_symbol(&["*"]).is_some() {
+      left = Expr::Binary(Op::Mul(), Box::new(left), Box::new(self.atom()?));
+    }
+    Ok(left)
+  }
+
+  fn atom(&mut self) -> Result<Expr, String> {
+    let token = self.peek().cloned().ok_or("Unexpected end of query")?;
+    self.position += 1;
+    match token {
+      Token::Number(number) => Ok(Expr::Number(number)),
+      Token::Symbol("(") => {
+        let inner = self.or()?;
+        self.expect_symbol(")")?;
+        Ok(inner)
+      },
+      Token::Symbol(symbol) => Err(format!("Unexpected {:?} at token {}", symbol, self.position)),
+      Token::Ident(ident) => {
+        let aggregate = match ident.as_str() {
+          "true" => return Ok(Expr::Bool(true)),
+          "false" => return Ok(Expr::Bool(false)),
+          "id" => return Ok(Expr::Id()),
+          "handfuls" => return Ok(Expr::Handfuls()),
+          "power" => return Ok(Expr::Power()),
+          "others" => return self.others(),
+          "any" => Some(Aggregate::Any()),
+          "all" => Some(Aggregate::All()),
+          "max" => Some(Aggregate::Max()),
+          "min" => Some(Aggregate::Min()),
+          "sum" => Some(Aggregate::Sum()),
+          _ => None,
+        };
+        match aggregate {
+          Some(aggregate) => {
+            if self.in_handful {
+              return Err(format!("{} can't be nested inside another any/all/max/min/sum", ident))
+            }
+            self.expect_symbol("(")?;
+            self.in_handful = true;
+            let inner = self.or()?;
+            self.in_handful = false;
+            self.expect_symbol(")")?;
+            Ok(Expr::Aggregate(aggregate, Box::new(inner)))
+          },
+          None if self.in_handful => Ok(Expr::Color(ident)),
+          None => Err(format!("{} counts cubes in a handful, so it needs any/all/max/min/sum around it", ident)),
+        }
+      },
+    }
+  }
+
+  fn others(&mut self) -> Result<Expr, String> {
+    if !self.in_handful {
+      return Err("others counts cubes in a handful, so it needs any/all/max/min/sum around it".into())
+    }
+    self.expect_symbol("(")?;
+    let mut colors = vec![];
+    while let Some(Token::Ident(color)) = self.peek().cloned() {
+      self.position += 1;
+      colors.push(color);
+      if self.accept_symbol(&[","]).is_none() {
+        break;
+      }
+    }
+    self.expect_symbol(")")?;
+    Ok(Expr::Others(colors))
+  }
+}
+
+pub fn parse(source: &str) -> Result<Expr, String> {
+  let mut parser = Parser { tokens: tokenize(source)?, position: 0, in_handful: false };
+  let expr = parser.or()?;
+  match parser.peek() {
+    None => Ok(expr),
+    Some(token) => Err(format!("Unexpected {:?} at token {}", token, parser.position + 1)),
+  }
+}
+
+struct Scope<'a> {
+  game: &'a Game,
+  handful: Option<&'a Handful>,
+}
+
+fn number(expr: &Expr, scope: &Scope) -> Result<i64, String> {
+  match evaluate_in(expr, scope)? {
+    Value::Number(number) => Ok(number),
+    Value::Bool(_) => Err(format!("Expected a number, got the condition {:?}", expr)),
+  }
+}
+
+fn condition(expr: &Expr, scope: &Scope) -> Result<bool, String> {
+  match evaluate_in(expr, scope)? {
+    Value::Bool(value) => Ok(value),
+    Value::Number(_) => Err(format!("Expected a condition, got the number {:?}", expr)),
+  }
+}
+
+fn evaluate_in(expr: &Expr, scope: &Scope) -> Result<Value, String> {
+  let handful = || scope.handful.ok_or(format!("{:?} needs a handful", expr));
+  Ok(match expr {
+    Expr::Number(number) => Value::Number(*number),
+    Expr::Bool(value) => Value::Bool(*value),
+    Expr::Id() => Value::Number(scope.game.id.into()),
+    Expr::Handfuls() => Value::Number(scope.game.handfuls.len() as i64),
+    Expr::Power() => Value::Number(game_power(scope.game).into()),
+    Expr::Color(color) => Value::Number(handful()?.count(color).into()),
+    Expr::Others(colors) => Value::Number(handful()?
+      .cubes
+      .iter()
+      .filter(|(color, _)| !colors.contains(color))
+      .map(|(_, count)| i64::from(*count))
+      .sum()),
+    Expr::Not(inner) => Value::Bool(!condition(inner, scope)?),
+    Expr::Binary(op, left, right) => match op {
+      Op::And() => Value::Bool(condition(left, scope)? && condition(right, scope)?),
+      Op::Or() => Value::Bool(condition(left, scope)? || condition(right, scope)?),
+      _ => {
+        let (left, right) = (number(left, scope)?, number(right, scope)?);
+        let overflow = || format!("{:?} overflows", expr);
+        match op {
+          Op::Add() => Value::Number(left.checked_add(right).ok_or_else(overflow)?),
+          Op::Sub() => Value::Number(left.checked_sub(right).ok_or_else(overflow)?),
+          Op::Mul() => Value::Number(left.checked_mul(right).ok_or_else(overflow)?),
+          Op::Lt() => Value::Bool(left < right),
+          Op::Le() => Value::Bool(left <= right),
+          Op::Gt() => Value::Bool(left > right),
+          Op::Ge() => Value::Bool(left >= right),
+          Op::Eq() => Value::Bool(left == right),
+          Op::Ne() => Value::Bool(left != right),
+          Op::And() | Op::Or() => unreachable!(),
+        }
+      },
+    },
+    Expr::Aggregate(aggregate, inner) => {
+      let mut handfuls = scope.game.handfuls.iter().map(|handful| Scope { handful: Some(handful), ..*scope });
+      match aggregate {
+        Aggregate::Any() => Value::Bool(handfuls.try_fold(false, |result, scope| Ok::<_, String>(result || condition(inner, &scope)?))?),
+        Aggregate::All() => Value::Bool(handfuls.try_fold(true, |result, scope| Ok::<_, String>(result && condition(inner, &scope)?))?),
+        _ => {
+          let numbers = handfuls.map(|scope| number(inner, &scope)).collect::<Result<Vec<i64>, String>>()?;
+          Value::Number(match aggregate {
+            // A game without handfuls needs no cubes at all
+            Aggregate::Max() => numbers.into_iter().max().unwrap_or(0),
+            Aggregate::Min() => numbers.into_iter().min().unwrap_or(0),
+            _ => numbers.into_iter().sum(),
+          })
+        },
+      }
+    },
+  })
+}
+
+pub fn matches(expr: &Expr, game: &Game) -> Result<bool, String> {
+  condition(expr, &Scope { game, handful: None })
+}
+
+// Adds up value over the games matching filter, which is how both puzzle answers come about
+pub fn total(games: &[Game], filter: &Expr, value: &Expr) -> Result<i64, String> {
+  games.iter().try_fold(0i64, |sum, game| {
+    if !matches(filter, game)? {
+      return Ok(sum)
+    }
+    let scope = Scope { game, handful: None };
+    sum.checked_add(number(value, &scope)?).ok_or("Total overflows".into())
+  })
+}
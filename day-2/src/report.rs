@@ -0,0 +1,157 @@
+// Per game statistics built on the minimum bag, plus a few aggregates across games.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use serde::Serialize;
+use crate::{Game, minimum_bag};
+
+#[derive(Debug,Serialize)]
+pub struct GameStats {
+  pub id: i32,
+  pub minimum_bag: BTreeMap<String, i32>,
+  pub power: i32,
+  pub totals: BTreeMap<String, i32>,
+  // The colors needing the most and the fewest cubes, ties going to the first alphabetically
+  pub most_constrained: Option<String>,
+  pub least_constrained: Option<String>,
+}
+
+#[derive(Debug,Serialize)]
+pub struct PowerDistribution {
+  pub min: i32,
+  pub max: i32,
+  pub mean: f64,
+  pub median: f64,
+  // How many games have each power
+  pub counts: BTreeMap<i32, usize>,
+}
+
+#[derive(Debug,Serialize)]
+pub struct SharedBag {
+  pub minimum_bag: BTreeMap<String, i32>,
+  pub games: Vec<i32>,
+}
+
+#[derive(Debug,Serialize)]
+pub struct Report {
+  pub games: Vec<GameStats>,
+  pub power_distribution: Option<PowerDistribution>,
+  pub shared_minimum_bags: Vec<SharedBag>,
+}
+
+fn game_stats(game: &Game, minimum_bag: BTreeMap<String, i32>) -> GameStats {
+  let totals = minimum_bag
+    .keys()
+    .map(|color| (color.clone(), game.handfuls.iter().map(|handful| handful.count(color)).sum()))
+    .collect();
+  let most_constrained = minimum_bag
+    .iter()
+    .max_by(|(a_color, a_count), (b_color, b_count)| a_count.cmp(b_count).then(b_color.cmp(a_color)))
+    .map(|(color, _)| color.clone());
+  let least_constrained = minimum_bag
+    .iter()
+    .min_by(|(a_color, a_count), (b_color, b_count)| a_count.cmp(b_count).then(a_color.cmp(b_color)))
+    .map(|(color, _)| color.clone());
+
+  GameStats {
+    id: game.id,
+    power: minimum_bag.values().product(),
+    minimum_bag,
+    totals,
+    most_constrained,
+    least_constrained,
+  }
+}
+
+fn power_distribution(games: &[GameStats]) -> Option<PowerDistribution> {
+  let mut powers: Vec<i32> = games.iter().map(|game| game.power).collect();
+  powers.sort();
+  let middle = powers.len() / 2;
+  let median = match powers.len() {
+    0 => return None,
+    length if length % 2 == 0 => (powers[middle - 1] as f64 + powers[middle] as f64) / 2.0,
+    _ => powers[middle] as f64,
+  };
+  let mut counts = BTreeMap::new();
+  for power in &powers {
+    *counts.entry(*power).or_insert(0) += 1;
+  }
+
+  Some(PowerDistribution {
+    min: powers[0],
+    max: powers[powers.len() - 1],
+    mean: powers.iter().map(|power| *power as f64).sum::<f64>() / powers.len() as f64,
+    median,
+    counts,
+  })
+}
+
+pub fn report(games: &[Game]) -> Report {
+  let games: Vec<GameStats> = games.iter().map(|game| game_stats(game, minimum_bag(game))).collect();
+
+  let mut by_bag: BTreeMap<&BTreeMap<String, i32>, Vec<i32>> = BTreeMap::new();
+  for game in &games {
+    by_bag.entry(&game.minimum_bag).or_default().push(game.id);
+  }
+  let shared_minimum_bags = by_bag
+    .into_iter()
+    .filter(|(_, ids)| ids.len() > 1)
+    .map(|(minimum_bag, games)| SharedBag { minimum_bag: minimum_bag.clone(), games })
+    .collect();
+
+  Report { power_distribution: power_distribution(&games), games, shared_minimum_bags }
+}
+
+fn format_bag(bag: &BTreeMap<String, i32>) -> String {
+  bag.iter().map(|(color, count)| format!("{}={}", color, count)).collect::<Vec<_>>().join(" ")
+}
+
+impl Report {
+  pub fn to_table(&self) -> String {
+    let rows: Vec<[String; 6]> = self
+      .games
+      .iter()
+      .map(|game| [
+        game.id.to_string(),
+        game.power.to_string(),
+        format_bag(&game.minimum_bag),
+        format_bag(&game.totals),
+        game.most_constrained.clone().unwrap_or_default(),
+        game.least_constrained.clone().unwrap_or_default(),
+      ])
+      .collect();
+    let header = ["game", "power", "minimum bag", "totals", "most", "least"].map(String::from);
+    let widths: Vec<usize> = (0 .. header.len())
+      .map(|column| rows.iter().chain([&header]).map(|row| row[column].len()).max().unwrap_or(0))
+      .collect();
+
+    let mut table = String::new();
+    for row in [&header].into_iter().chain(&rows) {
+      let cells: Vec<String> = row
+        .iter()
+        .zip(&widths)
+        .enumerate()
+        .map(|(column, (cell, width))| if column < 2 { format!("{:>width$}", cell) } else { format!("{:<width$}", cell) })
+        .collect();
+      writeln!(table, "{}", cells.join("  ").trim_end()).unwrap();
+    }
+
+    if let Some(distribution) = &self.power_distribution {
+      writeln!(table).unwrap();
+      writeln!(table, "power min {}, max {}, mean {:.2}, median {}", distribution.min, distribution.max, distribution.mean, distribution.median).unwrap();
+      for (power, count) in &distribution.counts {
+        writeln!(table, "{:>widths$}  {}", power, count, widths = widths[1]).unwrap();
+      }
+    }
+
+    if !self.shared_minimum_bags.is_empty() {
+      writeln!(table).unwrap();
+      writeln!(table, "shared minimum bags").unwrap();
+      for shared in &self.shared_minimum_bags {
+        let games: Vec<String> = shared.games.iter().map(i32::to_string).collect();
+        writeln!(table, "{}: games {}", format_bag(&shared.minimum_bag), games.join(", ")).unwrap();
+      }
+    }
+    table
+  }
+}
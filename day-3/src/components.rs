@@ -0,0 +1,126 @@
+// Groups numbers and parts into machines: two items belong together when any of their cells are
+// 8-adjacent, and so does anything reachable through a chain of such neighbours.
+
+use std::fmt;
+use crate::CollectParts;
+use crate::grid::Grid;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Member {
+  // An index into CollectParts::numbers, with its value
+  Number { index: usize, value: u32 },
+  Part { row: usize, col: usize, symbol: char },
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct BoundingBox {
+  pub top: usize,
+  pub left: usize,
+  pub bottom: usize,
+  pub right: usize,
+}
+
+#[derive(Debug,PartialEq)]
+pub struct Component {
+  // In reading order of each member's first cell
+  pub members: Vec<Member>,
+  pub bounding_box: BoundingBox,
+  pub sum: u64,
+  // The product of the numbers, None when it overflows. A component without numbers has product 0,
+  // as a part without adjacent numbers does under the product rule.
+  pub product: Option<u64>,
+}
+
+fn find(parents: &mut [usize], item: usize) -> usize {
+  let mut root = item;
+  while parents[root] != root {
+    root = parents[root];
+  }
+  let mut item = item;
+  while parents[item] != root {
+    let next = parents[item];
+    parents[item] = root;
+    item = next;
+  }
+  root
+}
+
+pub fn components(CollectParts { parts, numbers, number_references }: &CollectParts) -> Vec<Component> {
+  // Items are the numbers followed by the parts, mapped onto the cells they cover
+  let mut items: Grid<Option<usize>> = number_references.clone();
+  for (part, (row, col, _)) in parts.iter().enumerate() {
+    if let Some(cell) = items.get_mut(*row, *col) {
+      *cell = Some(numbers.len() + part);
+    }
+  }
+
+  let mut parents: Vec<usize> = (0 .. numbers.len() + parts.len()).collect();
+  for (row, col, item) in items.cells() {
+    let Some(item) = item else { continue };
+    for (neighbour_row, neighbour_col) in items.neighbours_8(row, col) {
+      if let Some(Some(neighbour)) = items.get(neighbour_row, neighbour_col) {
+        let (root, neighbour_root) = (find(&mut parents, *item), find(&mut parents, *neighbour));
+        parents[neighbour_root] = root;
+      }
+    }
+  }
+
+  // Walking the cells in reading order puts components, and the members within them, in that order
+  let mut components: Vec<Component> = vec![];
+  let mut component_of_root: Vec<Option<usize>> = vec![None; parents.len()];
+  let mut seen = vec![false; parents.len()];
+  for (row, col, item) in items.cells() {
+    let Some(item) = *item else { continue };
+    let root = find(&mut parents, item);
+    let index = *component_of_root[root].get_or_insert_with(|| {
+      components.push(Component {
+        members: vec![],
+        bounding_box: BoundingBox { top: row, left: col, bottom: row, right: col },
+        sum: 0,
+        product: Some(1),
+      });
+      components.len() - 1
+    });
+    let component = &mut components[index];
+    let bounding_box = &mut component.bounding_box;
+    bounding_box.left = bounding_box.left.min(col);
+    bounding_box.bottom = bounding_box.bottom.max(row);
+    bounding_box.right = bounding_box.right.max(col);
+    if seen[item] {
+      continue
+    }
+    seen[item] = true;
+    let member = match item.checked_sub(numbers.len()) {
+      None => Member::Number { index: item, value: numbers[item] },
+      Some(part) => Member::Part { row: parts[part].0, col: parts[part].1, symbol: parts[part].2 },
+    };
+    if let Member::Number { value, .. } = member {
+      component.sum += value as u64;
+      component.product = component.product.and_then(|product| product.checked_mul(value as u64));
+    }
+    component.members.push(member);
+  }
+  for component in &mut components {
+    if !component.members.iter().any(|member| matches!(member, Member::Number { .. })) {
+      component.product = Some(0);
+    }
+  }
+  components
+}
+
+// rows 0-2, cols 0-3: 467 * 35, sum 502, product 16345
+impl fmt::Display for Component {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let BoundingBox { top, left, bottom, right } = self.bounding_box;
+    let members: Vec<String> = self
+      .members
+      .iter()
+      .map(|member| match member {
+        Member::Number { value, .. } => value.to_string(),
+        Member::Part { symbol, .. } => symbol.to_string(),
+      })
+      .collect();
+    let product = self.product.map_or("too large".into(), |product| product.to_string());
+    write!(f, "rows {}-{}, cols {}-{}: {}, sum {}, product {}", top, bottom, left, right, members.join(" "), self.sum, product)
+  }
+}
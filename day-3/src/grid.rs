@@ -0,0 +1,112 @@
+// A rectangular grid addressed by (row, col), for the schematic here and the grid puzzles after it.
+// Every lookup is checked, so walking off an edge gives None or simply fewer neighbours.
+
+#[allow(dead_code)]
+const OFFSETS_4: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+const OFFSETS_8: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Grid<T> {
+  width: usize,
+  height: usize,
+  cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+  pub fn new(height: usize, width: usize, fill: T) -> Grid<T> {
+    Grid { width, height, cells: vec![fill; width * height] }
+  }
+
+  // Like parse, but lines may differ in length. The grid is as wide as the longest line, and
+  // shorter lines are padded with fill.
+  pub fn parse_ragged(text: &str, fill: T, mut to_cell: impl FnMut(char) -> T) -> Grid<T> {
+    let width = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    let mut cells = vec![];
+    for line in text.lines() {
+      let before = cells.len();
+      cells.extend(line.chars().map(&mut to_cell));
+      cells.resize(before + width, fill.clone());
+    }
+    Grid { width, height: text.lines().count(), cells }
+  }
+}
+
+impl<T> Grid<T> {
+  // One row per line, each char turned into a cell by to_cell. All lines must be the same length.
+  // This and the other helpers allowed dead code are for the grid puzzles after day 3.
+  #[allow(dead_code)]
+  pub fn parse(text: &str, mut to_cell: impl FnMut(char) -> T) -> Result<Grid<T>, String> {
+    let mut width = None;
+    let mut height = 0;
+    let mut cells = vec![];
+    for (row, line) in text.lines().enumerate() {
+      let before = cells.len();
+      cells.extend(line.chars().map(&mut to_cell));
+      let length = cells.len() - before;
+      if *width.get_or_insert(length) != length {
+        return Err(format!("Row {} has {} cells where row 1 has {}", row + 1, length, width.unwrap_or(0)))
+      }
+      height += 1;
+    }
+    Ok(Grid { width: width.unwrap_or(0), height, cells })
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  fn index(&self, row: usize, col: usize) -> Option<usize> {
+    (row < self.height && col < self.width).then_some(row * self.width + col)
+  }
+
+  pub fn get(&self, row: usize, col: usize) -> Option<&T> {
+    self.index(row, col).map(|index| &self.cells[index])
+  }
+
+  pub fn get_mut(&mut self, row: usize, col: usize) -> Option<&mut T> {
+    self.index(row, col).map(|index| &mut self.cells[index])
+  }
+
+  #[allow(dead_code)]
+  pub fn row(&self, row: usize) -> Option<&[T]> {
+    (row < self.height).then(|| &self.cells[row * self.width .. (row + 1) * self.width])
+  }
+
+  pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+    // chunks panics on 0, and a grid without columns has no cells to chunk anyway
+    self.cells.chunks(self.width.max(1))
+  }
+
+  #[allow(dead_code)]
+  pub fn column(&self, col: usize) -> Option<impl Iterator<Item = &T>> {
+    (col < self.width).then(|| self.cells.iter().skip(col).step_by(self.width))
+  }
+
+  // Every cell with its position, row by row
+  pub fn cells(&self) -> impl Iterator<Item = (usize, usize, &T)> {
+    self.cells.iter().enumerate().map(|(index, cell)| (index / self.width, index % self.width, cell))
+  }
+
+  fn offset(&self, row: usize, col: usize, offsets: &'static [(isize, isize)]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    offsets.iter().filter_map(move |(row_offset, col_offset)| {
+      let row = row.checked_add_signed(*row_offset)?;
+      let col = col.checked_add_signed(*col_offset)?;
+      self.index(row, col).map(|_| (row, col))
+    })
+  }
+
+  // Positions above, left, right and below that are inside the grid
+  #[allow(dead_code)]
+  pub fn neighbours_4(&self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    self.offset(row, col, &OFFSETS_4)
+  }
+
+  // Positions of the 4 neighbours and the diagonals that are inside the grid
+  pub fn neighbours_8(&self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    self.offset(row, col, &OFFSETS_8)
+  }
+}
@@ -1,8 +1,13 @@
+mod components;
+mod grid;
+mod rules;
+
 use std::fs;
+use grid::Grid;
 
 const UNIVERSAL_ERROR_MESSAGE: &str = "Something went wrong. Help!";
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug,Clone,PartialEq)]
 enum Cell {
   Digit(u32),
   Part(char),
@@ -25,49 +30,46 @@ enum State {
   ParsingSchemaNumber(SchemaNumber)
 }
 
-fn parse_line(line: &str) -> Vec<Cell> {
-  line.chars().map(|c| 
-    match c {
-      '.' => Cell::Empty(),
-      '0' ..= '9' => Cell::Digit(c.to_digit(10).unwrap()),
-      _ => Cell::Part(c)
-    }
-  ).into_iter().collect()
+fn parse_cell(c: char) -> Cell {
+  match c {
+    '.' => Cell::Empty(),
+    '0' ..= '9' => Cell::Digit(c.to_digit(10).unwrap()),
+    _ => Cell::Part(c)
+  }
 }
 
-fn parse_file(path: &str) -> Vec<Vec<Cell>> {
-  let file = fs::read_to_string(path).expect(UNIVERSAL_ERROR_MESSAGE);
-  file
-    .lines()
-    .map(|line| parse_line(line))
-    .collect()
+// Rows may differ in length, the missing cells past the end of a short row are empty
+fn parse_schematic(text: &str) -> Grid<Cell> {
+  Grid::parse_ragged(text, Cell::Empty(), parse_cell)
+}
+
+fn parse_file(path: &str) -> Grid<Cell> {
+  parse_schematic(&fs::read_to_string(path).expect(UNIVERSAL_ERROR_MESSAGE))
 }
 
 struct CollectParts {
   parts: Vec<(usize, usize, char)>,
   numbers: Vec<u32>,
-  number_references: Vec<Vec<Option<usize>>>
+  number_references: Grid<Option<usize>>
 }
 
-fn collect_parts(path: &str) -> CollectParts {
-  let schematic = parse_file(path);
+fn collect_parts(schematic: &Grid<Cell>) -> CollectParts {
   let mut state = State::Initial();
   let mut parts: Vec<(usize, usize, char)> = vec![];
   let mut numbers = vec![];
-  let mut number_references: Vec<Vec<Option<usize>>> = vec![vec![None; schematic[0].len()]; schematic.len()];
+  let mut number_references: Grid<Option<usize>> = Grid::new(schematic.height(), schematic.width(), None);
 
   let mut store_number =  |state: &State, row_number: usize| {
     if let State::ParsingSchemaNumber(schema_number) = state {
       numbers.push(schema_number.number);
       for col_number in schema_number.col_start ..= schema_number.col_end {
-        number_references[row_number][col_number] = Some(numbers.len() - 1);
+        *number_references.get_mut(row_number, col_number).expect(UNIVERSAL_ERROR_MESSAGE) = Some(numbers.len() - 1);
       }
     }
   };
 
-  for row_number in 0.. schematic.len() {
-    for col_number in 0.. schematic[row_number].len() {
-      let cell = &schematic[row_number][col_number];
+  for (row_number, row) in schematic.rows().enumerate() {
+    for (col_number, cell) in row.iter().enumerate() {
       match cell {
         Cell::Digit(digit) => {
           if let State::ParsingSchemaNumber(ref mut schema_number) = state {
@@ -101,63 +103,64 @@ fn collect_parts(path: &str) -> CollectParts {
   CollectParts { parts, numbers, number_references }
 }
 
-fn day_3_1(path: &str) -> u32 {
-  let CollectParts { parts, numbers, number_references } = collect_parts(path);
-
-  let selected_number_references: Vec<usize> = parts
-    .iter()
-    .map(|(row_number, col_number, _)| {
-      static EMPTY: Vec<Option<usize>> = vec![];
-      static OFFSETS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
-      OFFSETS
-        .map(|(row_offset, col_offset)| {
-          let peek_row_number = row_number.checked_add_signed(row_offset).unwrap();
-          let peek_col_number = col_number.checked_add_signed(col_offset).unwrap();
-          number_references.get(peek_row_number).unwrap_or(&EMPTY).get(peek_col_number)
-        })
-        .into_iter()
-        .filter(|item| if let Some(Some(_)) = item { true } else { false })
-        .map(|item| item.unwrap().unwrap())
-        .collect::<std::collections::HashSet<usize>>().into_iter().collect::<Vec<usize>>()
-    })
-    .flatten()
+// The numbers touching a cell, each once, even when several of their digits touch it
+fn adjacent_numbers(number_references: &Grid<Option<usize>>, row_number: usize, col_number: usize) -> Vec<usize> {
+  let mut references: Vec<usize> = number_references
+    .neighbours_8(row_number, col_number)
+    .filter_map(|(row, col)| *number_references.get(row, col)?)
     .collect();
-  
-  selected_number_references
-    .iter()
-    .map(|number_reference| numbers[*number_reference])
-    .fold(0, |sum, number| sum + number)
+  references.sort();
+  references.dedup();
+  references
 }
 
-fn day_3_2(path: &str) -> u32 {
-  let CollectParts { parts, numbers, number_references } = collect_parts(path);
-
-  parts
-    .iter()
-    .filter(|(_, _, c)| *c == '*')
-    .map(|(row_number, col_number, c)| {
-      static EMPTY: Vec<Option<usize>> = vec![];
-      static OFFSETS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
-      let part_number_references = OFFSETS
-        .map(|(row_offset, col_offset)| {
-          let peek_row_number = row_number.checked_add_signed(row_offset).unwrap();
-          let peek_col_number = col_number.checked_add_signed(col_offset).unwrap();
-          number_references.get(peek_row_number).unwrap_or(&EMPTY).get(peek_col_number)
-        })
-        .into_iter()
-        .filter(|item| if let Some(Some(_)) = item { true } else { false })
-        .map(|item| item.unwrap().unwrap())
-        .collect::<std::collections::HashSet<usize>>().into_iter().collect::<Vec<usize>>();
-      if part_number_references.len() == 2 {
-        numbers[part_number_references[0]] * numbers[part_number_references[1]]
-      } else {
-        0
-      }
-    })
-    .fold(0, |sum, number| sum + number)
+fn day_3_1(path: &str) -> u64 {
+  rules::apply(&rules::part_numbers(), &collect_parts(&parse_file(path))).expect(UNIVERSAL_ERROR_MESSAGE)
+}
+
+fn day_3_2(path: &str) -> u64 {
+  rules::apply(&rules::gear_ratios(), &collect_parts(&parse_file(path))).expect(UNIVERSAL_ERROR_MESSAGE)
+}
+
+fn usage() -> ! {
+  eprintln!("Usage: day-3 [--components] [--rule \"SYMBOLS COUNT REDUCER\"]... [--rules FILE]");
+  std::process::exit(1)
+}
+
+fn or_exit<T>(result: Result<T, String>) -> T {
+  result.unwrap_or_else(|error| {
+    eprintln!("{}", error);
+    std::process::exit(1)
+  })
 }
 
+// With --rule or --rules, prints the total for those rules instead of the two answers
 fn main() {
+  let mut show_components = false;
+  let mut custom_rules = vec![];
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--components" => show_components = true,
+      "--rule" => custom_rules.push(or_exit(rules::parse_rule(&args.next().unwrap_or_else(|| usage())))),
+      "--rules" => {
+        let path = args.next().unwrap_or_else(|| usage());
+        let text = or_exit(fs::read_to_string(&path).map_err(|error| format!("{}: {}", path, error)));
+        custom_rules.extend(or_exit(rules::parse_rules(&text)));
+      },
+      _ => usage(),
+    }
+  }
+
+  if show_components {
+    for component in components::components(&collect_parts(&parse_file("input"))) {
+      println!("{}", component);
+    }
+    return
+  }
+  if !custom_rules.is_empty() {
+    return println!("{}", or_exit(rules::apply(&custom_rules, &collect_parts(&parse_file("input")))))
+  }
   println!("1: {}", day_3_1("input"));
   println!("2: {}", day_3_2("input"));
 }
@@ -175,4 +178,140 @@ mod tests {
   fn day_3_2_handles_test_input() {
     assert_eq!(day_3_2("test_input"), 467835);
   }
+
+  #[test]
+  fn grid_lookups_are_checked() {
+    let grid = Grid::parse("abc\ndef", |c| c).unwrap();
+    assert_eq!((grid.height(), grid.width()), (2, 3));
+    assert_eq!(grid.get(1, 2), Some(&'f'));
+    assert_eq!(grid.get(2, 0), None);
+    assert_eq!(grid.get(0, 3), None);
+    assert_eq!(grid.row(1), Some(&['d', 'e', 'f'][..]));
+    assert_eq!(grid.row(2), None);
+    assert_eq!(grid.column(1).unwrap().collect::<String>(), "be");
+    assert!(grid.column(3).is_none());
+    assert_eq!(grid.rows().count(), 2);
+    assert_eq!(grid.cells().nth(4), Some((1, 1, &'e')));
+
+    assert_eq!(grid.neighbours_4(0, 0).collect::<Vec<_>>(), vec![(0, 1), (1, 0)]);
+    assert_eq!(grid.neighbours_4(1, 1).collect::<Vec<_>>(), vec![(0, 1), (1, 0), (1, 2)]);
+    assert_eq!(grid.neighbours_8(0, 2).collect::<Vec<_>>(), vec![(0, 1), (1, 1), (1, 2)]);
+    assert_eq!(grid.neighbours_8(1, 1).count(), 5);
+
+    let mut grid = Grid::new(2, 2, 0);
+    *grid.get_mut(1, 0).unwrap() = 7;
+    assert_eq!(grid.get_mut(2, 0), None);
+    assert_eq!(grid.rows().collect::<Vec<_>>(), vec![&[0, 0][..], &[7, 0][..]]);
+
+    assert_eq!(Grid::parse("ab\nc", |c| c), Err("Row 2 has 1 cells where row 1 has 2".into()));
+    assert_eq!(Grid::parse("", |c| c).map(|grid| (grid.height(), grid.width(), grid.rows().count())), Ok((0, 0, 0)));
+  }
+
+  fn both_parts(text: &str) -> (u64, u64) {
+    let parts = collect_parts(&parse_schematic(text));
+    (rules::apply(&rules::part_numbers(), &parts).unwrap(), rules::apply(&rules::gear_ratios(), &parts).unwrap())
+  }
+
+  #[test]
+  fn it_handles_symbols_in_corners() {
+    assert_eq!(both_parts("*1\n2."), (3, 2));
+    assert_eq!(both_parts("1*\n.2"), (3, 2));
+    assert_eq!(both_parts("2.\n*1"), (3, 2));
+    assert_eq!(both_parts(".2\n1*"), (3, 2));
+    assert_eq!(both_parts("*..\n...\n..9"), (0, 0));
+    assert_eq!(both_parts("*"), (0, 0));
+  }
+
+  #[test]
+  fn it_handles_symbols_on_edges() {
+    assert_eq!(both_parts("1*2\n...\n..."), (3, 2));
+    assert_eq!(both_parts("...\n...\n1*2"), (3, 2));
+    assert_eq!(both_parts("1..\n*..\n2.."), (3, 2));
+    assert_eq!(both_parts("..1\n..*\n..2"), (3, 2));
+    assert_eq!(both_parts("12*\n...\n*34"), (46, 0));
+  }
+
+  #[test]
+  fn it_handles_ragged_rows() {
+    assert_eq!(both_parts("..\n.*12\n3"), (15, 36));
+    assert_eq!(both_parts("12....\n*"), (12, 0));
+    assert_eq!(both_parts("..5\n7*..."), (12, 35));
+    // A number at the end of a short row doesn't run into the padding or the next row
+    assert_eq!(both_parts(".4\n6*..\n"), (10, 24));
+
+    let grid = Grid::parse_ragged("ab\n\nc", '.', |c| c);
+    assert_eq!((grid.height(), grid.width()), (3, 2));
+    assert_eq!(grid.rows().collect::<Vec<_>>(), vec![&['a', 'b'][..], &['.', '.'][..], &['c', '.'][..]]);
+  }
+
+  #[test]
+  fn it_finds_connected_components() {
+    let found = components::components(&collect_parts(&parse_file("test_input")));
+    let lines: Vec<String> = found.iter().map(|component| component.to_string()).collect();
+    assert_eq!(lines, vec![
+      "rows 0-2, cols 0-3: 467 * 35, sum 502, product 16345",
+      "rows 0-0, cols 5-7: 114, sum 114, product 114",
+      "rows 2-3, cols 6-8: 633 #, sum 633, product 633",
+      "rows 4-4, cols 0-3: 617 *, sum 617, product 617",
+      "rows 5-6, cols 2-5: + 592, sum 592, product 592",
+      "rows 5-5, cols 7-8: 58, sum 58, product 58",
+      "rows 7-9, cols 5-8: 755 * 598, sum 1353, product 451490",
+      "rows 8-9, cols 1-3: $ 664, sum 664, product 664",
+    ]);
+    assert_eq!(found[0].members[1], components::Member::Part { row: 1, col: 3, symbol: '*' });
+    assert_eq!(found[0].members[2], components::Member::Number { index: 2, value: 35 });
+
+    // Numbers touching each other chain up without a part in between
+    let found = components::components(&collect_parts(&parse_schematic("1...\n.2..\n..3*\n....\n9...")));
+    let lines: Vec<String> = found.iter().map(|component| component.to_string()).collect();
+    assert_eq!(lines, vec!["rows 0-2, cols 0-3: 1 2 3 *, sum 6, product 6", "rows 4-4, cols 0-0: 9, sum 9, product 9"]);
+
+    let found = components::components(&collect_parts(&parse_schematic("99999*99999*99999*99999*99999")));
+    assert_eq!(found[0].sum, 5 * 99999);
+    assert_eq!(found[0].product, None);
+    assert!(found[0].to_string().ends_with("product too large"));
+
+    // A lone part has an empty product, which is 0 here just as in the rules
+    let found = components::components(&collect_parts(&parse_schematic("*...\n....\n..*1")));
+    let lines: Vec<String> = found.iter().map(|component| component.to_string()).collect();
+    assert_eq!(lines, vec!["rows 0-0, cols 0-0: *, sum 0, product 0", "rows 2-2, cols 2-3: * 1, sum 1, product 1"]);
+    let rules = rules::parse_rules("* <=2 product").unwrap();
+    assert_eq!(rules::apply(&rules, &collect_parts(&parse_schematic("*..."))), Ok(found[0].product.unwrap()));
+  }
+
+  #[test]
+  fn it_applies_custom_rules() {
+    let parts = collect_parts(&parse_file("test_input"));
+    let total = |spec: &str| rules::apply(&rules::parse_rules(spec).unwrap(), &parts);
+    assert_eq!(total("* ==2 concat"), Ok(46735 + 755598));
+    assert_eq!(total("* >=1 max"), Ok(467 + 617 + 755));
+    assert_eq!(total("#+$ <=1 sum"), Ok(633 + 592 + 664));
+    assert_eq!(total("any ==1 product"), Ok(633 + 617 + 592 + 664));
+    // Each rule counts on its own, so both built-in rule sets together give the sum of the answers
+    assert_eq!(total("any >=1 sum\n\n* ==2 product\n"), Ok(4361 + 467835));
+    assert_eq!(total("* >=3 sum"), Ok(0));
+
+    // The lone * has nothing next to it, so it adds nothing rather than an empty product of 1
+    let parts = collect_parts(&parse_schematic("*...\n....\n..*1"));
+    for reducer in ["product", "sum", "max", "concat"] {
+      let rules = rules::parse_rules(&format!("* <=2 {}", reducer)).unwrap();
+      assert_eq!(rules::apply(&rules, &parts), Ok(1), "{}", reducer);
+    }
+
+    let parts = collect_parts(&parse_schematic("99999*99999\n99999.99999"));
+    assert!(rules::apply(&rules::parse_rules("* ==4 product").unwrap(), &parts).unwrap_err().contains("overflows for * ==4 product"));
+    assert_eq!(rules::apply(&rules::parse_rules("* ==4 sum").unwrap(), &parts), Ok(4 * 99999));
+  }
+
+  #[test]
+  fn it_parses_rules() {
+    assert_eq!(rules::gear_ratios()[0].to_string(), "* ==2 product");
+    assert_eq!(rules::parse_rule("any >=1 sum"), Ok(rules::part_numbers().remove(0)));
+    assert_eq!(rules::parse_rule("#$ <=3 max").unwrap().to_string(), "#$ <=3 max");
+    assert!(rules::parse_rule("* ==2").is_err());
+    assert!(rules::parse_rule("* =2 product").is_err());
+    assert!(rules::parse_rule("* ==x product").is_err());
+    assert!(rules::parse_rule("* ==2 average").is_err());
+    assert!(rules::parse_rules("* ==2 product\n\n* 2 sum").unwrap_err().starts_with("Line 3: "));
+  }
 }
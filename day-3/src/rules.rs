@@ -0,0 +1,156 @@
+// Rules picking out parts by symbol and by how many numbers touch them, and folding those numbers
+// into a value. The answer for a rule set is the sum of that value over every part each rule picks,
+// so a part picked by two rules counts twice.
+//
+// One rule per line, as SYMBOLS COUNT REDUCER:
+//   * ==2 product     gears, part 2
+//   any >=1 sum       part numbers, part 1
+//   #$ <=3 max
+// SYMBOLS is any, or the symbols themselves. COUNT is ==N, >=N or <=N adjacent numbers. REDUCER is
+// product, sum, max or concat, the last writing the numbers one after the other. A part with no
+// adjacent numbers is worth 0 under every reducer.
+
+use std::fmt;
+use crate::{CollectParts, adjacent_numbers};
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Symbols {
+  Any(),
+  Only(Vec<char>),
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Count {
+  Exact(usize),
+  Min(usize),
+  Max(usize),
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Reducer {
+  Product(),
+  Sum(),
+  Max(),
+  Concat(),
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Rule {
+  pub symbols: Symbols,
+  pub count: Count,
+  pub reducer: Reducer,
+}
+
+pub fn part_numbers() -> Vec<Rule> {
+  vec![Rule { symbols: Symbols::Any(), count: Count::Min(1), reducer: Reducer::Sum() }]
+}
+
+pub fn gear_ratios() -> Vec<Rule> {
+  vec![Rule { symbols: Symbols::Only(vec!['*']), count: Count::Exact(2), reducer: Reducer::Product() }]
+}
+
+impl Rule {
+  fn picks(&self, symbol: char, adjacent: usize) -> bool {
+    let symbol_matches = match &self.symbols {
+      Symbols::Any() => true,
+      Symbols::Only(symbols) => symbols.contains(&symbol),
+    };
+    let count_matches = match self.count {
+      Count::Exact(count) => adjacent == count,
+      Count::Min(count) => adjacent >= count,
+      Count::Max(count) => adjacent <= count,
+    };
+    symbol_matches && count_matches
+  }
+
+  fn reduce(&self, numbers: &[u64]) -> Option<u64> {
+    match self.reducer {
+      Reducer::Product() => match numbers.is_empty() {
+        true => Some(0),
+        false => numbers.iter().try_fold(1u64, |product, number| product.checked_mul(*number)),
+      },
+      Reducer::Sum() => numbers.iter().try_fold(0u64, |sum, number| sum.checked_add(*number)),
+      Reducer::Max() => Some(numbers.iter().copied().max().unwrap_or(0)),
+      Reducer::Concat() => match numbers.is_empty() {
+        true => Some(0),
+        false => numbers.iter().map(u64::to_string).collect::<String>().parse().ok(),
+      },
+    }
+  }
+}
+
+pub fn parse_rule(spec: &str) -> Result<Rule, String> {
+  let bad_rule = || format!("Expected SYMBOLS COUNT REDUCER, like \"* ==2 product\", got {:?}", spec);
+  let [symbols, count, reducer] = spec.split_whitespace().collect::<Vec<_>>()[..] else {
+    return Err(bad_rule())
+  };
+  let symbols = match symbols {
+    "any" => Symbols::Any(),
+    symbols => Symbols::Only(symbols.chars().collect()),
+  };
+  let number = |digits: &str| digits.parse().map_err(|_| bad_rule());
+  let count = if let Some(digits) = count.strip_prefix("==") {
+    Count::Exact(number(digits)?)
+  } else if let Some(digits) = count.strip_prefix(">=") {
+    Count::Min(number(digits)?)
+  } else if let Some(digits) = count.strip_prefix("<=") {
+    Count::Max(number(digits)?)
+  } else {
+    return Err(bad_rule())
+  };
+  let reducer = match reducer {
+    "product" => Reducer::Product(),
+    "sum" => Reducer::Sum(),
+    "max" => Reducer::Max(),
+    "concat" => Reducer::Concat(),
+    _ => return Err(bad_rule()),
+  };
+  Ok(Rule { symbols, count, reducer })
+}
+
+// A rule per line, blank lines skipped
+pub fn parse_rules(text: &str) -> Result<Vec<Rule>, String> {
+  text
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .map(|(index, line)| parse_rule(line).map_err(|error| format!("Line {}: {}", index + 1, error)))
+    .collect()
+}
+
+pub fn apply(rules: &[Rule], CollectParts { parts, numbers, number_references }: &CollectParts) -> Result<u64, String> {
+  let mut total: u64 = 0;
+  for (row_number, col_number, symbol) in parts {
+    let adjacent: Vec<u64> = adjacent_numbers(number_references, *row_number, *col_number)
+      .into_iter()
+      .map(|number_reference| numbers[number_reference] as u64)
+      .collect();
+    for rule in rules.iter().filter(|rule| rule.picks(*symbol, adjacent.len())) {
+      let overflow = || format!("The {} at row {}, col {} overflows for {}", symbol, row_number, col_number, rule);
+      let value = rule.reduce(&adjacent).ok_or_else(overflow)?;
+      total = total.checked_add(value).ok_or_else(overflow)?;
+    }
+  }
+  Ok(total)
+}
+
+impl fmt::Display for Rule {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match &self.symbols {
+      Symbols::Any() => write!(f, "any")?,
+      Symbols::Only(symbols) => write!(f, "{}", symbols.iter().collect::<String>())?,
+    }
+    match self.count {
+      Count::Exact(count) => write!(f, " =={}", count)?,
+      Count::Min(count) => write!(f, " >={}", count)?,
+      Count::Max(count) => write!(f, " <={}", count)?,
+    }
+    let reducer = match self.reducer {
+      Reducer::Product() => "product",
+      Reducer::Sum() => "sum",
+      Reducer::Max() => "max",
+      Reducer::Concat() => "concat",
+    };
+    write!(f, " {}", reducer)
+  }
+}
@@ -1,102 +1,468 @@
+mod trace;
+
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::fs::File;
 use std::ops::Range;
-use std::collections::HashSet;
-use std::pin::Pin;
+use std::cmp::{max, min};
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::marker::PhantomData;
+use std::collections::{HashMap, VecDeque};
+use trace::{trace, Level};
+
+trait AlmanacNumber: Copy + Ord + Debug + Display + FromStr {
+  const ZERO: Self;
+  const MIN: Self;
+  const MAX: Self;
+  const ONE: Self;
+  fn checked_add(self, other: Self) -> Option<Self>;
+  fn checked_sub(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_almanac_number {
+  ($($t:ty),*) => {
+    $(
+      impl AlmanacNumber for $t {
+        const ZERO: Self = 0;
+        const MIN: Self = <$t>::MIN;
+        const MAX: Self = <$t>::MAX;
+        const ONE: Self = 1;
+        fn checked_add(self, other: Self) -> Option<Self> { <$t>::checked_add(self, other) }
+        fn checked_sub(self, other: Self) -> Option<Self> { <$t>::checked_sub(self, other) }
+      }
+    )*
+  };
+}
+
+impl_almanac_number!(i32, i64, u32, u64);
+
+#[derive(Debug,PartialEq)]
+struct Overflow;
+
+#[derive(Debug,PartialEq)]
+enum AlmanacError {
+  Io(String),
+  BadNumber { line: usize, column: usize, token: String },
+  WrongColumnCount { line: usize, column: usize, expected: usize, found: usize },
+  BadHeading { line: usize, column: usize },
+  MissingHeading { line: usize, column: usize },
+  // A heading straight after another map, with no blank line to end it
+  UnendedMap { line: usize, column: usize },
+  MissingSeeds { line: usize, column: usize },
+  UnexpectedSeeds { line: usize, column: usize },
+  OddSeedCount { line: usize, column: usize },
+  SeedOverflow { line: usize, column: usize },
+  // Points at the zero length
+  EmptySeedRange { line: usize, column: usize },
+  Overflow,
+  BrokenChain { from: String, to: String, reached: Vec<String> },
+  Ambiguous(Vec<String>),
+}
+
+impl From<Overflow> for AlmanacError {
+  fn from(_: Overflow) -> Self {
+    AlmanacError::Overflow
+  }
+}
+
+impl Display for AlmanacError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AlmanacError::Io(message) => write!(f, "{}", message),
+      AlmanacError::BadNumber { line, column, token } =>
+        write!(f, "line {}, column {}: {:?} is not a valid number", line, column, token),
+      AlmanacError::WrongColumnCount { line, column, expected, found } =>
+        write!(f, "line {}, column {}: expected {} numbers, found {}", line, column, expected, found),
+      AlmanacError::BadHeading { line, column } =>
+        write!(f, "line {}, column {}: map heading should look like \"seed-to-soil map:\"", line, column),
+      AlmanacError::MissingHeading { line, column } => write!(f, "line {}, column {}: numbers before any map heading", line, column),
+      AlmanacError::UnendedMap { line, column } =>
+        write!(f, "line {}, column {}: map heading needs a blank line before it to end the previous map", line, column),
+      AlmanacError::MissingSeeds { line, column } => write!(f, "line {}, column {}: expected a \"seeds:\" line", line, column),
+      AlmanacError::UnexpectedSeeds { line, column } => write!(f, "line {}, column {}: only the first line may list seeds", line, column),
+      AlmanacError::OddSeedCount { line, column } => write!(f, "line {}, column {}: seed range is missing its length", line, column),
+      AlmanacError::SeedOverflow { line, column } => write!(f, "line {}, column {}: seed range overflows", line, column),
+      AlmanacError::EmptySeedRange { line, column } => write!(f, "line {}, column {}: seed range has no seeds", line, column),
+      AlmanacError::Overflow => write!(f, "Almanac arithmetic overflowed"),
+      AlmanacError::BrokenChain { from, to, reached } =>
+        write!(f, "No chain of maps leads from {} to {}, only reached: {}", from, to, reached.join(", ")),
+      AlmanacError::Ambiguous(problems) => write!(f, "Ambiguous almanac: {}", problems.join("; ")),
+    }
+  }
+}
 
-const UNIVERSAL_ERROR_MESSAGE: &str = "Something unexpected happened. Help!";
+fn checked_add<N: AlmanacNumber>(a: N, b: N) -> Result<N, Overflow> {
+  a.checked_add(b).ok_or(Overflow)
+}
+
+fn checked_sub<N: AlmanacNumber>(a: N, b: N) -> Result<N, Overflow> {
+  a.checked_sub(b).ok_or(Overflow)
+}
 
 enum ParseMode {
   Single(),
   Ranges(),
 }
 
-enum SplitRange {
-  Outside(Range<i32>),
-  Inside(Range<i32>),
+enum ValidationMode {
+  // Reject maps with overlapping, empty or colliding entries
+  Strict(),
+  // Let the first matching entry win
+  Lenient(),
 }
 
-fn split(seeds: Range<i32>, src_start: i32, length: i32) -> Vec<SplitRange> {
-  let src_end = src_start + length - 1;
-  let start_contained = seeds.contains(&src_start);
-  let end_contained = seeds.contains(&src_end);
-  match (start_contained, end_contained) {
-    (true, true) => vec![SplitRange::Inside(seeds)],
-    (true, false) => vec![SplitRange::Inside(seeds.start .. src_end), SplitRange::Outside(src_end .. seeds.end)],
-    (false, true) => vec![SplitRange::Outside(seeds.start .. src_start), SplitRange::Inside(src_start .. seeds.end)],
-    (false, false) => vec![SplitRange::Outside(seeds.start .. src_start), SplitRange::Inside(src_start .. src_end), SplitRange::Outside(src_end .. seeds.end)]
+#[derive(Debug)]
+enum SplitRange<N> {
+  Outside(Range<N>),
+  Inside(Range<N>),
+}
+
+fn split<N: AlmanacNumber>(seeds: Range<N>, src_start: N, length: N) -> Result<Vec<SplitRange<N>>, Overflow> {
+  let src_end = checked_add(src_start, length)?;
+  let inside = max(seeds.start, src_start) .. min(seeds.end, src_end);
+  if inside.is_empty() {
+    trace!("mapping", Level::Trace, "{:?} misses {:?}", seeds, src_start .. src_end);
+    return Ok(vec![SplitRange::Outside(seeds)])
+  }
+
+  let mut result = vec![];
+  if seeds.start < inside.start {
+    result.push(SplitRange::Outside(seeds.start .. inside.start));
+  }
+  if inside.end < seeds.end {
+    result.push(SplitRange::Outside(inside.end .. seeds.end));
   }
+  result.push(SplitRange::Inside(inside));
+  trace!("mapping", Level::Trace, "{:?} split by {:?} into {:?}", seeds, src_start .. src_end, result);
+  Ok(result)
 }
 
 #[derive(Debug)]
-enum Projection {
-  Mapped(Range<i32>),
-  UnMapped(Range<i32>),
+enum Projection<N> {
+  Mapped(Range<N>, Range<N>),
+  UnMapped(Range<N>),
 }
 
-fn project(seeds: Range<i32>, map: (i32, i32, i32)) -> Vec<Projection> {
+fn project<N: AlmanacNumber>(seeds: Range<N>, map: (N, N, N)) -> Result<Vec<Projection<N>>, Overflow> {
   let (dst_start, src_start, length) = map;
-  println!("{} - {}", dst_start, src_start);
-  let offset: i32 = (dst_start - src_start).try_into().unwrap();
-  split(seeds, src_start, length)
-    .iter()
+  let projections = split(seeds.clone(), src_start, length)?
+    .into_iter()
     .map(|split_range| match split_range {
-      SplitRange::Inside(seeds) => Projection::UnMapped(seeds.to_owned()),
-      SplitRange::Outside(seeds) => Projection::Mapped(seeds.start + offset  .. seeds.end + offset),
+      SplitRange::Inside(seeds) => {
+        // Offsets can be negative, so go via the distance from src_start to stay within unsigned types
+        let start = checked_add(dst_start, checked_sub(seeds.start, src_start)?)?;
+        let end = checked_add(dst_start, checked_sub(seeds.end, src_start)?)?;
+        Ok(Projection::Mapped(seeds, start .. end))
+      },
+      SplitRange::Outside(seeds) => Ok(Projection::UnMapped(seeds)),
     })
-    .collect()
+    .collect::<Result<Vec<_>, _>>()?;
+  trace!("mapping", Level::Trace, "{:?} through {:?} gives {:?}", seeds, map, projections);
+  Ok(projections)
 }
 
+// A source range together with the destination range it maps to
+type Piece<N> = (Range<N>, Range<N>);
+
 #[derive(Clone,Debug)]
-struct Mapper {
-  name: String,
-  maps: Vec<(i32, i32, i32)>
+struct Mapper<N> {
+  from: String,
+  to: String,
+  maps: Vec<(N, N, N)>,
+  line_numbers: Vec<usize>,
+}
+
+#[derive(Debug,PartialEq)]
+enum Ambiguity<N> {
+  Overlap { line_numbers: (usize, usize), src: Range<N> },
+  ZeroLength { line_number: usize },
+  DestinationCollision { line_numbers: (usize, usize), dst: Range<N> },
 }
 
-impl Mapper {
-  fn new() -> Mapper {
+impl<N: AlmanacNumber> Display for Ambiguity<N> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Ambiguity::Overlap { line_numbers: (first, second), src } =>
+        write!(f, "lines {} and {} both map sources {:?}", first, second, src),
+      Ambiguity::ZeroLength { line_number } => write!(f, "line {} has zero length", line_number),
+      Ambiguity::DestinationCollision { line_numbers: (first, second), dst } =>
+        write!(f, "lines {} and {} both map to {:?}", first, second, dst),
+    }
+  }
+}
+
+impl<N: AlmanacNumber> Mapper<N> {
+  fn new() -> Mapper<N> {
     Mapper {
-      name: String::new(),
+      from: String::new(),
+      to: String::new(),
       maps: vec![],
+      line_numbers: vec![],
     }
   }
 
-  fn add_map(&mut self, dst_start: i32, src_start: i32, length: i32) {
+  fn add_map(&mut self, line_number: usize, dst_start: N, src_start: N, length: N) {
     self.maps.push((dst_start, src_start, length));
+    self.line_numbers.push(line_number);
   }
 
-  fn map(&self, seed: Range<i32>) -> Vec<Range<i32>> {
-    let mut mapped: HashSet<Range<i32>> = HashSet::new();
-    let mut unmapped: HashSet<Range<i32>> = HashSet::from([seed]);
+  fn validate(&self) -> Result<Vec<Ambiguity<N>>, Overflow> {
+    let mut ambiguities = vec![];
+    for (index, &(dst_start, src_start, length)) in self.maps.iter().enumerate() {
+      let line_number = self.line_numbers[index];
+      if length == N::ZERO {
+        ambiguities.push(Ambiguity::ZeroLength { line_number });
+        continue;
+      }
+      for (other_index, &(other_dst_start, other_src_start, other_length)) in self.maps.iter().enumerate().skip(index + 1) {
+        let line_numbers = (line_number, self.line_numbers[other_index]);
+        let src = max(src_start, other_src_start) .. min(checked_add(src_start, length)?, checked_add(other_src_start, other_length)?);
+        if !src.is_empty() {
+          ambiguities.push(Ambiguity::Overlap { line_numbers, src });
+        }
+        let dst = max(dst_start, other_dst_start) .. min(checked_add(dst_start, length)?, checked_add(other_dst_start, other_length)?);
+        if !dst.is_empty() {
+          ambiguities.push(Ambiguity::DestinationCollision { line_numbers, dst });
+        }
+      }
+    }
+    Ok(ambiguities)
+  }
+
+  // Trims every entry down to the sources no earlier entry claims, which is what map does anyway
+  fn resolve(&self) -> Result<Mapper<N>, Overflow> {
+    let mut resolved = Mapper { from: self.from.clone(), to: self.to.clone(), ..Mapper::new() };
+    let mut claimed: Vec<(N, N)> = vec![];
+    for (index, &(dst_start, src_start, length)) in self.maps.iter().enumerate() {
+      if length == N::ZERO {
+        continue;
+      }
+      for src in subtract(src_start .. checked_add(src_start, length)?, &claimed)? {
+        let dst = checked_add(dst_start, checked_sub(src.start, src_start)?)?;
+        resolved.add_map(self.line_numbers[index], dst, src.start, checked_sub(src.end, src.start)?);
+      }
+      claimed.push((src_start, length));
+    }
+    Ok(resolved)
+  }
+
+  fn map(&self, seed: Range<N>) -> Result<Vec<Range<N>>, Overflow> {
+    Ok(self.map_pieces(seed)?.into_iter().map(|(_, dst)| dst).collect())
+  }
+
+  // Like map, but keeps track of which part of the seed range ended up where
+  fn map_pieces(&self, seed: Range<N>) -> Result<Vec<Piece<N>>, Overflow> {
+    let mut mapped: Vec<Piece<N>> = vec![];
+    let mut unmapped: Vec<Range<N>> = vec![seed];
 
     for map in &self.maps {
-      let unmapped_clone = unmapped.to_owned();
-      let mut remove_unmapped = vec![];
-      for seed in unmapped_clone {
-        for projection in project(seed.start .. seed.end, *map) {
+      let mut still_unmapped = vec![];
+      for seed in unmapped {
+        for projection in project(seed, *map)? {
           match projection {
-            Projection::Mapped(new_seed) => {
-              remove_unmapped.push(seed.start .. seed.end);
-              mapped.insert(new_seed);
-            },
-            _ => {}
+            Projection::Mapped(src, dst) => mapped.push((src, dst)),
+            Projection::UnMapped(seed) => still_unmapped.push(seed),
           }
         }
       }
-      for seed in remove_unmapped {
-        unmapped.remove(&seed);
+      unmapped = still_unmapped;
+    }
+
+    mapped.extend(unmapped.into_iter().map(|seed| (seed.clone(), seed)));
+    Ok(mapped)
+  }
+
+  // Runs the mapper backwards: every source range that ends up somewhere inside location
+  fn unmap(&self, location: Range<N>) -> Result<Vec<Range<N>>, Overflow> {
+    let mut result = vec![];
+    // Source ranges taken by earlier entries, since the first matching entry wins in map
+    let mut claimed: Vec<(N, N)> = vec![];
+
+    for &(dst_start, src_start, length) in &self.maps {
+      let dst_end = checked_add(dst_start, length)?;
+      let hit = max(location.start, dst_start) .. min(location.end, dst_end);
+      if !hit.is_empty() {
+        let src = checked_add(src_start, checked_sub(hit.start, dst_start)?)? .. checked_add(src_start, checked_sub(hit.end, dst_start)?)?;
+        result.extend(subtract(src, &claimed)?);
+      }
+      claimed.push((src_start, length));
+    }
+
+    // Whatever no entry claims is passed through unchanged
+    result.extend(subtract(location, &claimed)?);
+    Ok(result)
+  }
+}
+
+fn subtract<N: AlmanacNumber>(seeds: Range<N>, claimed: &[(N, N)]) -> Result<Vec<Range<N>>, Overflow> {
+  claimed.iter().try_fold(vec![seeds], |remaining, &(src_start, length)| {
+    let mut result = vec![];
+    for seeds in remaining {
+      for split_range in split(seeds, src_start, length)? {
+        if let SplitRange::Outside(seeds) = split_range {
+          result.push(seeds);
+        }
+      }
+    }
+    Ok(result)
+  })
+}
+
+// Picks the maps leading from one category to another, whatever order they were listed in
+fn chain<N: AlmanacNumber>(mappers: &[Mapper<N>], from: &str, to: &str) -> Result<Vec<Mapper<N>>, AlmanacError> {
+  let mut reached: HashMap<&str, Option<usize>> = HashMap::from([(from, None)]);
+  let mut queue = VecDeque::from([from]);
+  while let Some(category) = queue.pop_front() {
+    if category == to {
+      break;
+    }
+    for (index, mapper) in mappers.iter().enumerate() {
+      if mapper.from == category && !reached.contains_key(mapper.to.as_str()) {
+        reached.insert(&mapper.to, Some(index));
+        queue.push_back(&mapper.to);
       }
     }
-    
-    vec![]
+  }
+
+  if !reached.contains_key(to) {
+    let mut reached: Vec<String> = reached.into_keys().map(String::from).collect();
+    reached.sort();
+    return Err(AlmanacError::BrokenChain { from: from.into(), to: to.into(), reached })
+  }
+
+  let mut result = vec![];
+  let mut category = to;
+  while let Some(Some(index)) = reached.get(category) {
+    result.push(mappers[*index].clone());
+    category = &mappers[*index].from;
+  }
+  result.reverse();
+  Ok(result)
+}
+
+fn validated<N: AlmanacNumber>(mappers: Vec<Mapper<N>>, validation_mode: &ValidationMode) -> Result<Vec<Mapper<N>>, AlmanacError> {
+  mappers
+    .into_iter()
+    .map(|mapper| match validation_mode {
+      ValidationMode::Strict() => {
+        let ambiguities = mapper.validate()?;
+        if ambiguities.is_empty() {
+          Ok(mapper)
+        } else {
+          Err(AlmanacError::Ambiguous(ambiguities
+            .iter()
+            .map(|ambiguity| format!("{}-to-{} map: {}", mapper.from, mapper.to, ambiguity))
+            .collect()))
+        }
+      },
+      ValidationMode::Lenient() => {
+        for ambiguity in mapper.validate()? {
+          trace!("validation", Level::Warn, "{}-to-{} map: {}, first match wins", mapper.from, mapper.to, ambiguity);
+        }
+        Ok(mapper.resolve()?)
+      },
+    })
+    .collect()
+}
+
+// A single function converting between any two categories, e.g. soil to humidity
+fn conversion<N: AlmanacNumber>(mappers: &[Mapper<N>], from: &str, to: &str) -> Result<Composition<N>, AlmanacError> {
+  Ok(Composition::compose(&chain(mappers, from, to)?)?)
+}
+
+fn unmap_chain<N: AlmanacNumber>(mappers: &[Mapper<N>], locations: Range<N>) -> Result<Vec<Range<N>>, Overflow> {
+  mappers.iter().rev().try_fold(vec![locations], |acc, mapper| {
+    acc
+      .into_iter()
+      .map(|locations| mapper.unmap(locations))
+      .collect::<Result<Vec<_>, _>>()
+      .map(|unmapped| unmapped.concat())
+  })
+}
+
+#[derive(Clone,Debug,PartialEq)]
+struct Segment<N> {
+  src: Range<N>,
+  dst_start: N,
+}
+
+// A chain of mappers collapsed into one sorted table of segments covering N::MIN .. N::MAX.
+// The offset of each segment is kept as a destination start so unsigned types can shift down, and
+// no segment is longer than N::MAX so its length can always be worked out.
+#[derive(Clone,Debug)]
+struct Composition<N> {
+  segments: Vec<Segment<N>>,
+}
+
+impl<N: AlmanacNumber> Composition<N> {
+  fn identity() -> Composition<N> {
+    // Signed types span more than N::MAX, so they start out in three segments
+    let mut segments = vec![];
+    let mut start = N::MIN;
+    while start < N::MAX {
+      let end = start.checked_add(N::MAX).unwrap_or(N::MAX);
+      segments.push(Segment { src: start .. end, dst_start: start });
+      start = end;
+    }
+    Composition { segments }
+  }
+
+  fn then(&self, mapper: &Mapper<N>) -> Result<Composition<N>, Overflow> {
+    let mut segments = vec![];
+    for segment in &self.segments {
+      let length = checked_sub(segment.src.end, segment.src.start)?;
+      let image = segment.dst_start .. checked_add(segment.dst_start, length)?;
+      for (piece, dst) in mapper.map_pieces(image)? {
+        let src_start = checked_add(segment.src.start, checked_sub(piece.start, segment.dst_start)?)?;
+        let src_end = checked_add(segment.src.start, checked_sub(piece.end, segment.dst_start)?)?;
+        segments.push(Segment { src: src_start .. src_end, dst_start: dst.start });
+      }
+    }
+    segments.sort_by_key(|segment| segment.src.start);
+
+    let mut merged: Vec<Segment<N>> = vec![];
+    for segment in segments {
+      if let Some(last) = merged.last_mut() {
+        let continues = checked_sub(last.src.end, last.src.start)
+          .and_then(|length| checked_add(last.dst_start, length))
+          .is_ok_and(|last_dst_end| last.src.end == segment.src.start && last_dst_end == segment.dst_start)
+          && checked_sub(segment.src.end, last.src.start).is_ok();
+        if continues {
+          last.src.end = segment.src.end;
+          continue;
+        }
+      }
+      merged.push(segment);
+    }
+
+    Ok(Composition { segments: merged })
+  }
+
+  fn compose<'a>(mappers: impl IntoIterator<Item = &'a Mapper<N>>) -> Result<Composition<N>, Overflow> where N: 'a {
+    mappers
+      .into_iter()
+      .try_fold(Composition::identity(), |composition, mapper| composition.then(mapper))
+  }
+
+  fn segment_index(&self, value: N) -> usize {
+    self.segments.partition_point(|segment| segment.src.end <= value)
+  }
+
+  fn apply(&self, value: N) -> Result<N, Overflow> {
+    match self.segments.get(self.segment_index(value)) {
+      Some(segment) if segment.src.contains(&value) =>
+        checked_add(segment.dst_start, checked_sub(value, segment.src.start)?),
+      // Only N::MAX falls outside the table, and as ranges end before it no map can reach it
+      _ => Ok(value),
+    }
   }
 }
 
 #[derive(Debug)]
-enum AlmanacItem {
-  Seeds(Vec<Range<i32>>),
-  Map(Mapper),
+enum AlmanacItem<N> {
+  Seeds(Vec<Range<N>>),
+  Map(Mapper<N>),
 }
 
 enum State {
@@ -105,154 +471,549 @@ enum State {
   Done(),
 }
 
-struct AlmanacIterator {
-  reader: BufReader<File>,
+struct AlmanacIterator<N> {
+  reader: Box<dyn BufRead>,
+  line_number: usize,
   state: State,
   parse_mode: ParseMode,
+  number_type: PhantomData<N>,
 }
 
-fn parse_file(path: &str, parse_mode: ParseMode) -> AlmanacIterator {
-  let file = File::open(path).expect(UNIVERSAL_ERROR_MESSAGE);
-  let reader = BufReader::new(file);
-  AlmanacIterator { reader, state: State::Initial(), parse_mode }
+fn parse_file<N>(path: &str, parse_mode: ParseMode) -> Result<AlmanacIterator<N>, AlmanacError> {
+  let file = File::open(path).map_err(|error| AlmanacError::Io(format!("{}: {}", path, error)))?;
+  Ok(parse_reader(BufReader::new(file), parse_mode))
 }
 
-impl AlmanacIterator {
-  fn next_line(&mut self) -> Option<String> {
+fn parse_reader<N>(reader: impl BufRead + 'static, parse_mode: ParseMode) -> AlmanacIterator<N> {
+  AlmanacIterator { reader: Box::new(reader), line_number: 0, state: State::Initial(), parse_mode, number_type: PhantomData }
+}
+
+impl<N> AlmanacIterator<N> {
+  fn next_line(&mut self) -> Result<Option<String>, AlmanacError> {
     let mut buffer = String::new();
 
-    let read_length = &self
+    let read_length = self
       .reader
       .read_line(&mut buffer)
-      .expect(UNIVERSAL_ERROR_MESSAGE);
+      .map_err(|error| AlmanacError::Io(format!("line {}: {}", self.line_number + 1, error)))?;
 
     match read_length {
-      0 => None,
-      _ => Some(buffer)
+      0 => Ok(None),
+      _ => {
+        self.line_number += 1;
+        Ok(Some(buffer))
+      }
     }
   }
 }
 
-enum Line {
+// Numbers come with the column they start in
+enum Line<N> {
   Empty(),
-  MapHeading(String),
-  Numbers(Vec<i32>)
+  Seeds(Vec<(usize, N)>),
+  MapHeading(String, String),
+  Numbers(Vec<(usize, N)>)
+}
+
+// "seed-to-soil map:" becomes ("seed", "soil")
+fn parse_heading(line: &str) -> Option<(String, String)> {
+  let (from, to) = line.trim().strip_suffix("map:")?.trim().split_once("-to-")?;
+  Some((from.into(), to.into()))
+}
+
+fn parse_numbers<N: AlmanacNumber>(line: &str, line_number: usize, offset: usize) -> Result<Vec<(usize, N)>, AlmanacError> {
+  let mut tokens = vec![];
+  let mut token_start = None;
+  for (index, c) in line.char_indices() {
+    match (c.is_whitespace(), token_start) {
+      (true, Some(start)) => {
+        tokens.push((start, &line[start .. index]));
+        token_start = None;
+      },
+      (false, None) => token_start = Some(index),
+      _ => {}
+    }
+  }
+  if let Some(start) = token_start {
+    tokens.push((start, &line[start ..]));
+  }
+
+  tokens
+    .into_iter()
+    .map(|(start, token)| {
+      let column = offset + start + 1;
+      token
+        .parse::<N>()
+        .map(|number| (column, number))
+        .map_err(|_| AlmanacError::BadNumber { line: line_number, column, token: token.into() })
+    })
+    .collect()
 }
 
-fn parse_line(line: &str) -> Line {
-  if line.trim().len() == 0 {
-    return Line::Empty()
+fn parse_line<N: AlmanacNumber>(line: &str, line_number: usize) -> Result<Line<N>, AlmanacError> {
+  if line.trim().is_empty() {
+    return Ok(Line::Empty())
   }
 
-  if line.contains("map:") {
-    return Line::MapHeading(line.trim().into())
+  if let Some(numbers) = line.strip_prefix("seeds:") {
+    return Ok(Line::Seeds(parse_numbers(numbers, line_number, "seeds:".len())?))
   }
 
-  let numbers: Vec<i32> = line
-    .split(|c| c > '9' || c < '0')
-    .filter(|token| token.trim().len() > 0)
-    .map(|token| token.parse::<i32>().unwrap())
-    .collect();
+  if line.contains("map:") {
+    let (from, to) = parse_heading(line).ok_or(AlmanacError::BadHeading { line: line_number, column: 1 })?;
+    return Ok(Line::MapHeading(from, to))
+  }
 
-  Line::Numbers(numbers)
+  Ok(Line::Numbers(parse_numbers(line, line_number, 0)?))
 }
 
-impl Iterator for AlmanacIterator {
-  type Item = AlmanacItem;
-  
-  fn next(&mut self) -> Option<Self::Item> {
+impl<N: AlmanacNumber> AlmanacIterator<N> {
+  fn seed_ranges(&self, numbers: Vec<(usize, N)>) -> Result<Vec<Range<N>>, AlmanacError> {
+    let line = self.line_number;
+    match self.parse_mode {
+      ParseMode::Single() => numbers
+        .into_iter()
+        .map(|(column, number)| Ok(number .. checked_add(number, N::ONE).map_err(|_| AlmanacError::SeedOverflow { line, column })?))
+        .collect(),
+      ParseMode::Ranges() => numbers
+        .chunks(2)
+        .map(|pair| match pair {
+          // An empty range has no seeds to map, but its start would still be taken as the lowest
+          [_, (column, length)] if *length == N::ZERO => Err(AlmanacError::EmptySeedRange { line, column: *column }),
+          [(column, start), (_, length)] => Ok(*start .. checked_add(*start, *length).map_err(|_| AlmanacError::SeedOverflow { line, column: *column })?),
+          [(column, _)] => Err(AlmanacError::OddSeedCount { line, column: *column }),
+          _ => unreachable!(),
+        })
+        .collect(),
+    }
+  }
+
+  fn next_item(&mut self) -> Result<Option<AlmanacItem<N>>, AlmanacError> {
     if let State::Done() = self.state {
-      return None
+      return Ok(None)
     }
 
     let mut mapper = Mapper::new();
     loop {
-      let Some(buffer) = self.next_line() else {
-        self.state = State::Done();
-        break Some(AlmanacItem::Map(mapper))
+      let Some(buffer) = self.next_line()? else {
+        return match std::mem::replace(&mut self.state, State::Done()) {
+          State::Initial() => Err(AlmanacError::MissingSeeds { line: self.line_number + 1, column: 1 }),
+          // Nothing but blank lines after the last map
+          _ if mapper.from.is_empty() => Ok(None),
+          _ => Ok(Some(AlmanacItem::Map(mapper))),
+        }
       };
 
-      let line = parse_line(&buffer);
+      let line_number = self.line_number;
+      let line = parse_line(&buffer, line_number)?;
 
       match self.state {
         State::ParsingMap() => {
           match line {
             Line::Empty() => {
-              break Some(AlmanacItem::Map(mapper))
+              if !mapper.from.is_empty() {
+                return Ok(Some(AlmanacItem::Map(mapper)))
+              }
             },
-            Line::MapHeading(name) => {
-              mapper.name = name
+            Line::Seeds(_) => {
+              return Err(AlmanacError::UnexpectedSeeds { line: line_number, column: 1 })
+            },
+            Line::MapHeading(from, to) => {
+              if !mapper.from.is_empty() {
+                return Err(AlmanacError::UnendedMap { line: line_number, column: 1 })
+              }
+              trace!("parse", Level::Debug, "line {}: {}-to-{} map", line_number, from, to);
+              mapper.from = from;
+              mapper.to = to;
             },
             Line::Numbers(numbers) => {
-              mapper.add_map(numbers[0], numbers[1], numbers[2]);
+              if mapper.from.is_empty() {
+                return Err(AlmanacError::MissingHeading { line: line_number, column: numbers[0].0 })
+              }
+              let [(_, dst_start), (_, src_start), (_, length)] = numbers[..] else {
+                let column = numbers.get(3).map_or(buffer.trim_end().len() + 1, |(column, _)| *column);
+                return Err(AlmanacError::WrongColumnCount { line: line_number, column, expected: 3, found: numbers.len() })
+              };
+              trace!("parse", Level::Trace, "line {}: {} {} {}", line_number, dst_start, src_start, length);
+              mapper.add_map(line_number, dst_start, src_start, length);
             }
           }
         },
         State::Initial() => {
           match line {
-            Line::Numbers(numbers) => {
-              self.next_line();
-              self.state = State::ParsingMap();
-              break match self.parse_mode {
-                ParseMode::Single() => Some(AlmanacItem::Seeds(numbers.iter().map(|number| *number .. number + 1).collect())),
-                ParseMode::Ranges() => Some(AlmanacItem::Seeds(numbers.chunks(2).map(|pair| pair[0] .. pair[0] + pair[1]).collect())),
+            Line::Empty() => {},
+            Line::Seeds(numbers) => {
+              if numbers.is_empty() {
+                return Err(AlmanacError::MissingSeeds { line: line_number, column: buffer.trim_end().len() + 1 })
               }
-              
+              self.state = State::ParsingMap();
+              let seeds = self.seed_ranges(numbers)?;
+              trace!("parse", Level::Debug, "line {}: seeds {:?}", line_number, seeds);
+              return Ok(Some(AlmanacItem::Seeds(seeds)))
             },
-            _ => {}
+            _ => return Err(AlmanacError::MissingSeeds { line: line_number, column: 1 }),
           }
         },
-        State::Done() => {}
+        State::Done() => return Ok(None),
       }
     }
   }
-}
 
-fn day_5_1(path: &str) -> i32 {
-  let mut almanac_iterator = parse_file(path, ParseMode::Single());
-  let Some(AlmanacItem::Seeds(seeds)) = almanac_iterator.next() else { panic!("{}", UNIVERSAL_ERROR_MESSAGE) };
-  let result = almanac_iterator.fold(seeds, |acc, item| {
-    let AlmanacItem::Map(mapper) = item else { panic!("{} - {:?}", UNIVERSAL_ERROR_MESSAGE, item) };
-    acc
-      .into_iter()
-      .map(|seed| mapper.map(seed))
-      .flatten()
+  fn seeds(&mut self) -> Result<Vec<Range<N>>, AlmanacError> {
+    match self.next() {
+      Some(Ok(AlmanacItem::Seeds(seeds))) => Ok(seeds),
+      Some(Err(error)) => Err(error),
+      _ => Err(AlmanacError::MissingSeeds { line: self.line_number, column: 1 }),
+    }
+  }
+
+  // The remaining maps, so take the seeds first
+  fn mappers(self) -> Result<Vec<Mapper<N>>, AlmanacError> {
+    self
+      .filter_map(|item| match item {
+        Ok(AlmanacItem::Map(mapper)) => Some(Ok(mapper)),
+        Ok(AlmanacItem::Seeds(_)) => None,
+        Err(error) => Some(Err(error)),
+      })
       .collect()
-  });
-  result.iter().map(|range| range.start).min().unwrap()
+  }
+}
+
+impl<N: AlmanacNumber> Iterator for AlmanacIterator<N> {
+  type Item = Result<AlmanacItem<N>, AlmanacError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let item = self.next_item();
+    if let Err(error) = &item {
+      trace!("parse", Level::Error, "{}", error);
+      self.state = State::Done();
+    }
+    item.transpose()
+  }
+}
+
+// "35" for a single location or "35..40", end exclusive, for a range
+fn parse_locations<N: AlmanacNumber>(spec: &str) -> Option<Range<N>> {
+  match spec.split_once("..") {
+    Some((start, end)) => Some(start.trim().parse().ok()? .. end.trim().parse().ok()?),
+    None => {
+      let location: N = spec.trim().parse().ok()?;
+      Some(location .. location.checked_add(N::ONE)?)
+    },
+  }
 }
 
-fn day_5_2(path: &str) -> i32 {
-  let mut almanac_iterator = parse_file(path, ParseMode::Ranges());
-  let Some(AlmanacItem::Seeds(seeds)) = almanac_iterator.next() else { panic!("{}", UNIVERSAL_ERROR_MESSAGE) };
-  let result = almanac_iterator.fold(seeds, |acc, item| {
-    let AlmanacItem::Map(mapper) = item else { panic!("{} - {:?}", UNIVERSAL_ERROR_MESSAGE, item) };
-    println!("Using mapper: {:?}", mapper);
+// Every seed number, listed in the almanac or not, that ends up somewhere in locations
+fn seeds_for<N: AlmanacNumber>(path: &str, locations: Range<N>) -> Result<Vec<Range<N>>, AlmanacError> {
+  let mut almanac_iterator = parse_file(path, ParseMode::Single())?;
+  almanac_iterator.seeds()?;
+  let mappers = chain(&almanac_iterator.mappers()?, "seed", "location")?;
+  let mut seeds = unmap_chain(&mappers, locations)?;
+  seeds.sort_by_key(|seeds| seeds.start);
+  Ok(seeds)
+}
+
+// Checks a candidate answer by running the almanac backwards: some seed has to reach location,
+// and no seed may reach anything below it
+fn is_lowest_location<N: AlmanacNumber>(path: &str, parse_mode: ParseMode, location: N) -> Result<bool, AlmanacError> {
+  let mut almanac_iterator = parse_file(path, parse_mode)?;
+  let seeds = almanac_iterator.seeds()?;
+  let mappers = chain(&almanac_iterator.mappers()?, "seed", "location")?;
+  let reaches = |locations: Range<N>| -> Result<bool, Overflow> {
+    Ok(unmap_chain(&mappers, locations)?
+      .iter()
+      .any(|candidates| seeds.iter().any(|seeds| max(seeds.start, candidates.start) < min(seeds.end, candidates.end))))
+  };
+  Ok(reaches(location .. checked_add(location, N::ONE)?)? && !reaches(N::ZERO .. location)?)
+}
+
+fn day_5_1<N: AlmanacNumber>(path: &str, validation_mode: ValidationMode) -> Result<N, AlmanacError> {
+  let mut almanac_iterator = parse_file(path, ParseMode::Single())?;
+  let seeds = almanac_iterator.seeds()?;
+  let composition = conversion(&validated(almanac_iterator.mappers()?, &validation_mode)?, "seed", "location")?;
+  trace!("mapping", Level::Debug, "seed to location in {} segments: {:?}", composition.segments.len(), composition.segments);
+  let locations = seeds
+    .iter()
+    .map(|seed| composition.apply(seed.start))
+    .collect::<Result<Vec<N>, _>>()?;
+  Ok(locations.into_iter().min().expect("The parser never yields an empty seeds list"))
+}
+
+fn day_5_2<N: AlmanacNumber>(path: &str, validation_mode: ValidationMode) -> Result<N, AlmanacError> {
+  let mut almanac_iterator = parse_file(path, ParseMode::Ranges())?;
+  let seeds = almanac_iterator.seeds()?;
+  let mappers = chain(&validated(almanac_iterator.mappers()?, &validation_mode)?, "seed", "location")?;
+  let result = mappers.iter().try_fold(seeds, |acc, mapper| {
+    trace!("mapping", Level::Debug, "{} ranges through {}-to-{} map", acc.len(), mapper.from, mapper.to);
     acc
       .into_iter()
       .map(|seed| mapper.map(seed))
-      .flatten()
-      .collect()
-  });
-  result.iter().map(|range| range.start).min().unwrap()
+      .collect::<Result<Vec<_>, _>>()
+      .map(|mapped| mapped.concat())
+  })?;
+  Ok(result.iter().map(|range| range.start).min().expect("The parser never yields an empty seeds list"))
+}
+
+fn or_exit<T>(path: &str, result: Result<T, AlmanacError>) -> T {
+  result.unwrap_or_else(|error| {
+    eprintln!("{}: {}", path, error);
+    std::process::exit(1)
+  })
 }
 
+// Usage: day-5 [--lenient] [--seeds-for LOCATION]
+// Usage: day-5 [--lenient] [--seeds-for LOCATION|START..END] [--check-lowest LOCATION]
 fn main() {
-  println!("{}", day_5_1("input"));
-  println!("{}", day_5_2("input"));
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let value_of = |flag: &str| args.iter().position(|arg| arg == flag).map(|position| args.get(position + 1).cloned().unwrap_or_default());
+  let usage_error = |message: &str| -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2)
+  };
+  if let Some(spec) = value_of("--seeds-for") {
+    let locations = parse_locations::<u64>(&spec).unwrap_or_else(|| usage_error("--seeds-for needs a location or START..END"));
+    for seeds in or_exit("input", seeds_for("input", locations)) {
+      println!("{}..{}", seeds.start, seeds.end);
+    }
+    return
+  }
+  if let Some(spec) = value_of("--check-lowest") {
+    let location: u64 = spec.parse().unwrap_or_else(|_| usage_error("--check-lowest needs a location"));
+    for (part, parse_mode) in [(1, ParseMode::Single()), (2, ParseMode::Ranges())] {
+      println!("{}: {}", part, or_exit("input", is_lowest_location("input", parse_mode, location)));
+    }
+    return
+  }
+  let validation_mode = || if args.iter().any(|arg| arg == "--lenient") { ValidationMode::Lenient() } else { ValidationMode::Strict() };
+  println!("{}", or_exit("input", day_5_1::<u64>("input", validation_mode())));
+  println!("{}", or_exit("input", day_5_2::<u64>("input", validation_mode())));
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use std::io::Cursor;
+
+  fn test_input_mappers() -> Vec<Mapper<u64>> {
+    let mut almanac_iterator = parse_file::<u64>("test_input", ParseMode::Single()).unwrap();
+    almanac_iterator.seeds().unwrap();
+    almanac_iterator.mappers().unwrap()
+  }
+
+  fn parse_error(input: &'static str, parse_mode: ParseMode) -> AlmanacError {
+    let mut almanac_iterator = parse_reader::<u64>(Cursor::new(input), parse_mode);
+    almanac_iterator
+      .seeds()
+      .and_then(|_| almanac_iterator.mappers())
+      .unwrap_err()
+  }
+
+  #[test]
+  fn mapper_splits_ranges_and_passes_through_unmapped() {
+    let mut mapper = Mapper::new();
+    mapper.add_map(1, 50, 98, 2);
+    mapper.add_map(2, 52, 50, 48);
+    let mut result = mapper.map(45 .. 100).unwrap();
+    result.sort_by_key(|range| range.start);
+    assert_eq!(result, vec![45 .. 50, 50 .. 52, 52 .. 100]);
+  }
+
+  #[test]
+  fn composition_agrees_with_fold() {
+    let mappers = test_input_mappers();
+    let composition = Composition::compose(&mappers).unwrap();
+    assert!(composition.segments.windows(2).all(|pair| pair[0].src.end == pair[1].src.start));
+
+    for seed in 0 .. 200 {
+      let folded = mappers
+        .iter()
+        .fold(seed .. seed + 1, |seeds, mapper| mapper.map(seeds).unwrap()[0].clone());
+      assert_eq!(composition.apply(seed), Ok(folded.start));
+    }
+
+    let mut mapper = Mapper::<i64>::new();
+    mapper.add_map(1, 100, -10, 5);
+    let mappers = [mapper.clone(), mapper];
+    let composition = Composition::compose(&mappers).unwrap();
+    for seed in [i64::MIN, -11, -10, -8, -6, -5, 0, 102, i64::MAX - 1] {
+      let folded = mappers
+        .iter()
+        .fold(seed .. seed + 1, |seeds, mapper| mapper.map(seeds).unwrap()[0].clone());
+      assert_eq!(composition.apply(seed), Ok(folded.start));
+    }
+    assert_eq!(composition.apply(-8), Ok(102));
+  }
+
+  #[test]
+  fn mapper_unmaps_ranges_and_passes_through_unclaimed() {
+    let mut mapper = Mapper::new();
+    mapper.add_map(1, 50, 98, 2);
+    mapper.add_map(2, 52, 50, 48);
+    let mut result = mapper.unmap(45 .. 100).unwrap();
+    result.sort_by_key(|range| range.start);
+    // 50 .. 52 comes from 98 .. 100, but 50 and 51 themselves are claimed, so only 45 .. 50 passes through
+    assert_eq!(result, vec![45 .. 50, 50 .. 98, 98 .. 100]);
+    assert_eq!(mapper.unmap(50 .. 51).unwrap(), vec![98 .. 99]);
+  }
+
+  #[test]
+  fn unmap_chain_finds_test_input_seeds() {
+    let mappers = test_input_mappers();
+    let composition = Composition::compose(&mappers).unwrap();
+    for location in 0 .. 200 {
+      for seeds in unmap_chain(&mappers, location .. location + 1).unwrap() {
+        for seed in seeds {
+          assert_eq!(composition.apply(seed), Ok(location));
+        }
+      }
+    }
+    assert!(unmap_chain(&mappers, 35 .. 36).unwrap().contains(&(13 .. 14)));
+    assert!(seeds_for::<u64>("test_input", 35 .. 36).unwrap().contains(&(13 .. 14)));
+    assert_eq!(parse_locations::<u64>("35"), Some(35 .. 36));
+    assert_eq!(parse_locations::<u64>("35..40"), Some(35 .. 40));
+    assert_eq!(parse_locations::<u64>("35..x"), None);
+    // Seeds 13 and 14 land at 35 and 43, while 79 lands at 82
+    let seeds = seeds_for::<u64>("test_input", 35 .. 44).unwrap();
+    assert!([13, 14].iter().all(|seed| seeds.iter().any(|seeds| seeds.contains(seed))));
+    assert!(!seeds.iter().any(|seeds| seeds.contains(&79)));
+    assert_eq!(is_lowest_location::<u64>("test_input", ParseMode::Single(), 35), Ok(true));
+    assert_eq!(is_lowest_location::<u64>("test_input", ParseMode::Single(), 43), Ok(false));
+    assert_eq!(is_lowest_location::<u64>("test_input", ParseMode::Ranges(), 46), Ok(true));
+  }
 
   #[test]
   fn day_5_1_handles_test_input() {
-    assert_eq!(day_5_1("test_input"), 35);
+    assert_eq!(day_5_1::<u64>("test_input", ValidationMode::Strict()), Ok(35));
   }
 
   #[test]
   fn day_5_2_handles_test_input() {
-    assert_eq!(day_5_2("test_input"), 46);
+    assert_eq!(day_5_2::<u64>("test_input", ValidationMode::Strict()), Ok(46));
+  }
+
+  #[test]
+  fn day_5_handles_test_input_with_signed_numbers() {
+    assert_eq!(day_5_1::<i64>("test_input", ValidationMode::Strict()), Ok(35));
+    assert_eq!(day_5_2::<i32>("test_input", ValidationMode::Strict()), Ok(46));
+  }
+
+  #[test]
+  fn day_5_reorders_shuffled_maps() {
+    assert_eq!(day_5_1::<u64>("test_input_shuffled", ValidationMode::Strict()), Ok(35));
+    assert_eq!(day_5_2::<u64>("test_input_shuffled", ValidationMode::Strict()), Ok(46));
+  }
+
+  #[test]
+  fn day_5_reports_broken_chain() {
+    let error = day_5_1::<u64>("test_input_broken", ValidationMode::Strict()).unwrap_err();
+    assert_eq!(error, AlmanacError::BrokenChain {
+      from: "seed".into(),
+      to: "location".into(),
+      reached: vec!["fertilizer".into(), "seed".into(), "soil".into()],
+    });
+    assert_eq!(error.to_string(), "No chain of maps leads from seed to location, only reached: fertilizer, seed, soil");
+  }
+
+  #[test]
+  fn conversion_between_any_categories() {
+    let mappers = test_input_mappers();
+    assert_eq!(conversion(&mappers, "soil", "humidity").unwrap().apply(81), Ok(78));
+    assert_eq!(conversion(&mappers, "light", "light").unwrap().apply(74), Ok(74));
+    assert!(conversion(&mappers, "location", "seed").is_err());
+  }
+
+  #[test]
+  fn mapper_reports_ambiguities() {
+    let mut mapper = Mapper::new();
+    mapper.add_map(4, 100, 10, 10);
+    mapper.add_map(5, 200, 15, 10);
+    mapper.add_map(6, 105, 40, 3);
+    mapper.add_map(7, 300, 50, 0);
+    assert_eq!(mapper.validate(), Ok(vec![
+      Ambiguity::Overlap { line_numbers: (4, 5), src: 15 .. 20 },
+      Ambiguity::DestinationCollision { line_numbers: (4, 6), dst: 105 .. 108 },
+      Ambiguity::ZeroLength { line_number: 7 },
+    ]));
+
+    let resolved = mapper.resolve().unwrap();
+    assert_eq!(resolved.maps, vec![(100, 10, 10), (205, 20, 5), (105, 40, 3)]);
+    assert_eq!(resolved.line_numbers, vec![4, 5, 6]);
+    for seed in 0 .. 60 {
+      assert_eq!(resolved.map(seed .. seed + 1), mapper.map(seed .. seed + 1));
+    }
+  }
+
+  #[test]
+  fn day_5_validation_modes() {
+    let error = day_5_1::<u64>("test_input_overlapping", ValidationMode::Strict()).unwrap_err();
+    assert_eq!(error.to_string(), "Ambiguous almanac: seed-to-soil map: lines 5 and 6 both map sources 60..70");
+    // The extra entry on line 6 is shadowed by line 5, so first match wins gives the usual answers
+    assert_eq!(day_5_1::<u64>("test_input_overlapping", ValidationMode::Lenient()), Ok(35));
+    assert_eq!(day_5_2::<u64>("test_input_overlapping", ValidationMode::Lenient()), Ok(46));
+  }
+
+  #[test]
+  fn parser_reports_errors_with_positions() {
+    assert_eq!(
+      parse_error("seeds: 79 1x4\n", ParseMode::Single()),
+      AlmanacError::BadNumber { line: 1, column: 11, token: "1x4".into() });
+    assert_eq!(
+      parse_error("seeds: 79 14 55\n", ParseMode::Ranges()),
+      AlmanacError::OddSeedCount { line: 1, column: 14 });
+    assert_eq!(
+      parse_error("seeds: 18446744073709551615 1\n", ParseMode::Ranges()),
+      AlmanacError::SeedOverflow { line: 1, column: 8 });
+    assert_eq!(
+      parse_error("seeds: 5 0 79 14\n", ParseMode::Ranges()),
+      AlmanacError::EmptySeedRange { line: 1, column: 10 });
+    assert_eq!(
+      parse_error("\nseed-to-soil map:\n50 98 2\n", ParseMode::Single()),
+      AlmanacError::MissingSeeds { line: 2, column: 1 });
+    assert_eq!(
+      parse_error("seeds:\n", ParseMode::Single()),
+      AlmanacError::MissingSeeds { line: 1, column: 7 });
+    assert_eq!(
+      parse_error("seeds: 79\n\nseed-to-soil map:\n50 98\n", ParseMode::Single()),
+      AlmanacError::WrongColumnCount { line: 4, column: 6, expected: 3, found: 2 });
+    assert_eq!(
+      parse_error("seeds: 79\n\nseed-to-soil map:\n50 98 2 7\n", ParseMode::Single()),
+      AlmanacError::WrongColumnCount { line: 4, column: 9, expected: 3, found: 4 });
+    assert_eq!(
+      parse_error("seeds: 79\n\n50 98 2\n", ParseMode::Single()),
+      AlmanacError::MissingHeading { line: 3, column: 1 });
+    assert_eq!(
+      parse_error("seeds: 79\n\nseed-to-soil map:\n50 0 5\nsoil-to-location map:\n0 50 1\n", ParseMode::Single()),
+      AlmanacError::UnendedMap { line: 5, column: 1 });
+    assert_eq!(
+      parse_error("seeds: 79\n\nseed-to-soil map:\nsoil-to-location map:\n0 50 1\n", ParseMode::Single()),
+      AlmanacError::UnendedMap { line: 4, column: 1 });
+    assert_eq!(
+      parse_error("seeds: 79\n\nseed to soil map:\n", ParseMode::Single()),
+      AlmanacError::BadHeading { line: 3, column: 1 });
+    assert_eq!(
+      parse_error("seeds: 79\n\nseeds: 80\n", ParseMode::Single()),
+      AlmanacError::UnexpectedSeeds { line: 3, column: 1 });
+    assert_eq!(
+      parse_error("seeds: 79\n\nseed-to-soil map:\n50 98 -2\n", ParseMode::Single()).to_string(),
+      "line 4, column 7: \"-2\" is not a valid number");
+  }
+
+  #[test]
+  fn parser_stops_after_first_error() {
+    let mut almanac_iterator = parse_reader::<u64>(Cursor::new("seeds: x\nseeds: 1\n"), ParseMode::Single());
+    assert!(matches!(almanac_iterator.next(), Some(Err(AlmanacError::BadNumber { .. }))));
+    assert!(almanac_iterator.next().is_none());
+  }
+
+  #[test]
+  fn missing_file_is_an_error() {
+    assert!(matches!(day_5_1::<u64>("no_such_input", ValidationMode::Strict()), Err(AlmanacError::Io(_))));
+  }
+
+  #[test]
+  fn mapper_reports_overflow() {
+    let mut mapper = Mapper::new();
+    mapper.add_map(1, u32::MAX - 1, 0, 10);
+    assert_eq!(mapper.map(0 .. 5), Err(Overflow));
   }
 }
\ No newline at end of file
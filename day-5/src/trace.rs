@@ -0,0 +1,100 @@
+// Leveled diagnostics on stderr, so stdout only ever carries the answers.
+//
+// Switched on through the AOC_TRACE environment variable, a comma separated list of directives:
+//   AOC_TRACE=debug                             everything at debug and above
+//   AOC_TRACE=day-5=info                        one day
+//   AOC_TRACE=mapping=trace                     one subsystem, whatever the day
+//   AOC_TRACE=day-5:parse=debug,day-5:mapping=trace
+// The most specific directive matching a day and subsystem wins.
+
+use std::sync::OnceLock;
+
+pub const DAY: &str = env!("CARGO_PKG_NAME");
+
+#[derive(Clone,Copy,Debug,PartialEq,PartialOrd)]
+pub enum Level {
+  Error,
+  Warn,
+  Info,
+  Debug,
+  Trace,
+}
+
+fn parse_level(level: &str) -> Option<Level> {
+  match level.trim().to_ascii_lowercase().as_str() {
+    "error" => Some(Level::Error),
+    "warn" => Some(Level::Warn),
+    "info" => Some(Level::Info),
+    "debug" => Some(Level::Debug),
+    "trace" => Some(Level::Trace),
+    _ => None,
+  }
+}
+
+#[derive(Debug,PartialEq)]
+struct Directive {
+  day: Option<String>,
+  subsystem: Option<String>,
+  level: Level,
+}
+
+// Directives that don't parse are ignored rather than taking the answers down with them
+fn parse_directives(spec: &str) -> Vec<Directive> {
+  spec
+    .split(',')
+    .filter_map(|directive| {
+      let Some((target, level)) = directive.rsplit_once('=') else {
+        return parse_level(directive).map(|level| Directive { day: None, subsystem: None, level })
+      };
+      let level = parse_level(level)?;
+      let target = target.trim();
+      let (day, subsystem) = match target.split_once(':') {
+        Some((day, subsystem)) => (Some(day.into()), Some(subsystem.into())),
+        None if target.starts_with("day") => (Some(target.into()), None),
+        None => (None, Some(target.into())),
+      };
+      Some(Directive { day, subsystem, level })
+    })
+    .collect()
+}
+
+fn level_for(directives: &[Directive], day: &str, subsystem: &str) -> Option<Level> {
+  directives
+    .iter()
+    .filter(|directive| directive.day.iter().all(|d| d == day))
+    .filter(|directive| directive.subsystem.iter().all(|s| s == subsystem))
+    .max_by_key(|directive| directive.day.is_some() as u8 + directive.subsystem.is_some() as u8)
+    .map(|directive| directive.level)
+}
+
+pub fn enabled(subsystem: &str, level: Level) -> bool {
+  static DIRECTIVES: OnceLock<Vec<Directive>> = OnceLock::new();
+  let directives = DIRECTIVES.get_or_init(|| parse_directives(&std::env::var("AOC_TRACE").unwrap_or_default()));
+  level_for(directives, DAY, subsystem).is_some_and(|max_level| level <= max_level)
+}
+
+macro_rules! trace {
+  ($subsystem:expr, $level:expr, $($arg:tt)*) => {
+    if $crate::trace::enabled($subsystem, $level) {
+      eprintln!("[{} {} {:?}] {}", $crate::trace::DAY, $subsystem, $level, format_args!($($arg)*));
+    }
+  };
+}
+
+pub(crate) use trace;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn most_specific_directive_wins() {
+    let directives = parse_directives("warn,day-5=info,mapping=debug,day-5:mapping=trace,bogus");
+    assert_eq!(directives.len(), 4);
+    assert_eq!(level_for(&directives, "day-5", "mapping"), Some(Level::Trace));
+    assert_eq!(level_for(&directives, "day-5", "parse"), Some(Level::Info));
+    assert_eq!(level_for(&directives, "day-4", "mapping"), Some(Level::Debug));
+    assert_eq!(level_for(&directives, "day-4", "parse"), Some(Level::Warn));
+    assert_eq!(level_for(&parse_directives(""), "day-5", "parse"), None);
+  }
+}